@@ -1,11 +1,68 @@
-use crate::prfitem::PrfItem;
+use std::collections::HashMap;
+
+use crate::{
+    clash::controller::{Connections, Delay, ProxyInfo},
+    prfitem::{PrfItem, PrfOption, PrfRevision},
+};
 
 #[derive(Debug, Clone)]
 pub enum Action {
     SubScription(String), // 订阅事件
+    /// 新增的订阅链接已存在, 参数为已有订阅的 uid 和名称
+    SubScriptionExists(String, String),
+    /// 刷新已有订阅, 参数为 uid
+    SubScriptionRefresh(String),
+    /// 导入本地配置文件, 参数为文件路径
+    LocalProfile(String),
+    /// 新建 merge 配置, 参数为名称
+    MergeProfile(String),
+    /// 新建 script 配置, 参数为名称
+    ScriptProfile(String),
     SubScriptionResult(PrfItem),
     Error(String),
+    /// 不影响使用的问题, 如配置校验警告
+    Warning(String),
     SubScriptionUpdate,
     UpdatePrfList(Vec<PrfItem>),
     SelectedItem(String),
+    /// 设置订阅自动更新间隔, 参数为 uid 和分钟数
+    SetUpdateInterval(String, u64),
+    /// 删除配置及其文件, 参数为 uid
+    DeleteProfile(String),
+    /// 重命名配置, 参数为 uid 和新名称
+    RenameProfile(String, String),
+    /// 设置订阅的 http 配置, 参数为 uid 和配置
+    SetPrfOption(String, PrfOption),
+    /// 加载订阅的配置文件内容, 参数为 uid
+    LoadProfile(String),
+    /// 订阅的配置文件内容, 参数为 uid 和内容
+    ProfileData(String, String),
+    /// 加载订阅的历史版本, 参数为 uid
+    LoadRevisions(String),
+    /// 订阅的历史版本, 参数为 uid 和按时间从新到旧排列的版本, 第一项为当前内容
+    Revisions(String, Vec<PrfRevision>),
+    /// 回滚到历史版本, 参数为 uid 和版本 id
+    RollbackProfile(String, i64),
+    /// 在外部编辑器中编辑配置文件, 参数为 uid
+    EditProfile(String),
+    /// script 配置运行失败, 参数为 uid 和错误信息
+    ScriptError(String, String),
+    /// 从内核获取节点和策略组
+    LoadProxies,
+    /// 内核中的节点和策略组, 以名称为键
+    ProxiesData(HashMap<String, ProxyInfo>),
+    /// 切换策略组使用的节点, 参数为策略组和节点名称
+    SelectProxy(String, String),
+    /// 在后台并发测试节点的延迟, 参数为节点名称
+    TestDelay(Vec<String>),
+    /// 一个节点的测试结果, 参数为节点名称和结果
+    DelayResult(String, Delay),
+    /// 开始在后台接收内核的连接信息, 离开连接页面后停止
+    WatchConnections,
+    /// 内核当前的连接
+    ConnectionsData(Connections),
+    /// 关闭连接, 参数为连接 id
+    CloseConnection(String),
+    /// 关闭所有连接
+    CloseConnections,
 }
@@ -1,14 +1,24 @@
 use crate::{
     action::Action,
-    config::{get_subscribe_dir, Config},
-    db,
-    menu::{subscription::SubScription, version::Version},
+    clash::{
+        self,
+        controller::{self, Controller, Delay},
+    },
+    config::Config,
+    db::{self, PrfRepo},
+    enhance::{self, ScriptError},
+    menu::{
+        connections::Connections, proxies::Proxies, subscription::SubScription, version::Version,
+    },
     mode::Mode,
-    prfitem::PrfItem,
+    prfitem::{PrfItem, PrfRevision},
+    scheduler,
+    utils::help,
     view::View,
 };
 use color_eyre::{eyre::eyre, Result};
 use crossterm::event::{self, Event, KeyCode};
+use futures::{stream, StreamExt};
 use ratatui::crossterm::{
     cursor,
     event::{DisableMouseCapture, EnableMouseCapture},
@@ -20,7 +30,13 @@ use ratatui::{
     widgets::{Block, Paragraph},
     Frame,
 };
-use tracing::debug;
+use serde_yaml::Mapping;
+use std::time::Duration;
+use tokio::{
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    task::JoinHandle,
+};
+use tracing::{debug, warn};
 
 pub struct App {
     config: Config,
@@ -29,17 +45,38 @@ pub struct App {
     mode: Mode,
     menus: Vec<Box<dyn View>>,
     info: String,
+    action_tx: UnboundedSender<Action>,
+    action_rx: UnboundedReceiver<Action>,
+    repo: PrfRepo,
+    /// 从外部程序返回后需要清屏重绘
+    redraw: bool,
+    /// 内核的 external-controller 客户端, 激活配置后重新创建
+    controller: Option<Controller>,
+    /// 在后台接收连接信息的任务, 只在显示连接页面时运行
+    connections_task: Option<JoinHandle<()>>,
 }
 
 impl App {
-    pub fn new() -> Result<Self> {
+    pub fn new(repo: PrfRepo) -> Result<Self> {
+        let (action_tx, action_rx) = mpsc::unbounded_channel();
         Ok(Self {
             should_quit: false,
             config: Config::new()?,
             menu_index: 0,
             mode: Mode::Version,
-            menus: vec![Box::new(Version::new()), Box::new(SubScription::new())],
+            menus: vec![
+                Box::new(Version::new()),
+                Box::new(SubScription::new()),
+                Box::new(Proxies::new()),
+                Box::new(Connections::new()),
+            ],
             info: "提示信息".to_string(),
+            action_tx,
+            action_rx,
+            repo,
+            redraw: false,
+            controller: None,
+            connections_task: None,
         })
     }
 
@@ -102,6 +139,11 @@ impl App {
                 menu.set_focus();
             }
         }
+        if !matches!(self.mode, Mode::Connections) {
+            if let Some(task) = self.connections_task.take() {
+                task.abort();
+            }
+        }
     }
 
     pub async fn run(&mut self) -> Result<()> {
@@ -109,13 +151,26 @@ impl App {
         self.enter()?;
         self.set_focus();
         let mut terminal = ratatui::init();
+        let scheduler = scheduler::spawn(
+            self.repo.clone(),
+            self.action_tx.clone(),
+            self.config.config.fetch.clone(),
+        );
         while !self.should_quit {
+            if std::mem::take(&mut self.redraw) {
+                terminal.clear()?;
+            }
             terminal.draw(|f| self.draw(f))?;
             self.handle_events()?;
             // 获取当前获取焦点的事件
             let action = self.current_menus().get_events();
             self.handle_actions(action).await?;
+            // 处理后台任务发送的事件
+            while let Ok(action) = self.action_rx.try_recv() {
+                self.handle_actions(Some(action)).await?;
+            }
         }
+        scheduler.abort();
         debug!("程序退出");
         Ok(())
     }
@@ -125,49 +180,507 @@ impl App {
             return Ok(());
         }
         let res_action = match action.unwrap() {
-            Action::SubScription(url) => {
-                let res = PrfItem::from_url(&url).await;
-                let action = match res {
-                    Ok(item) => {
-                        let filename = item.file.clone();
-                        let file_data = item.file_data.clone();
-                        let mut sub_dir = get_subscribe_dir();
-                        if filename.is_none() {
-                            return Err(eyre!("订阅文件名为空"));
-                        }
-                        sub_dir.push(format!("{}.yaml", filename.unwrap()));
-                        if file_data.is_none() {
-                            return Err(eyre!("订阅文件数据为空"));
-                        }
-                        debug!("订阅文件路径:{}", sub_dir.display());
-                        if !sub_dir.exists() {
-                            tokio::fs::create_dir_all(sub_dir.parent().unwrap()).await?;
-                        }
-                        tokio::fs::write(sub_dir, file_data.unwrap()).await?;
-                        db::insert_prf_item(&item).await?;
-                        Action::SubScriptionResult(item)
-                    }
-                    Err(err) => Action::Error(format!("{err:?}")),
+            Action::SubScription(url) => match self.repo.get_by_url(&url).await? {
+                // 链接已存在时询问是否更新已有订阅
+                Some(item) => Some(Action::SubScriptionExists(
+                    item.uid.unwrap_or_default(),
+                    item.name.unwrap_or("config".to_string()),
+                )),
+                None => {
+                    let item = PrfItem::from_url(url.trim(), &self.config.config.fetch).await;
+                    Some(self.create_profile(item).await?)
+                }
+            },
+            Action::LocalProfile(path) => Some(
+                self.create_profile(PrfItem::from_local(&path).await)
+                    .await?,
+            ),
+            Action::MergeProfile(name) => {
+                Some(self.create_profile(PrfItem::from_merge(&name)).await?)
+            }
+            Action::ScriptProfile(name) => {
+                Some(self.create_profile(PrfItem::from_script(&name)).await?)
+            }
+            Action::SelectedItem(uid) => self.activate(&uid).await?,
+            Action::SubScriptionRefresh(uid) => {
+                let fetch = &self.config.config.fetch;
+                Some(scheduler::refresh_prf_item(&self.repo, &uid, fetch, &self.action_tx).await?)
+            }
+            Action::SetPrfOption(uid, option) => {
+                self.repo.set_option(&uid, &option).await?;
+                self.set_info("订阅 http 配置已保存, 下次更新时生效".to_string());
+                Some(Action::UpdatePrfList(self.repo.list().await?))
+            }
+            Action::SetUpdateInterval(uid, interval) => {
+                self.repo.set_interval(&uid, interval).await?;
+                Some(Action::UpdatePrfList(self.repo.list().await?))
+            }
+            Action::DeleteProfile(uid) => match self.repo.delete(&uid).await {
+                Ok(_) => Some(Action::UpdatePrfList(self.repo.list().await?)),
+                Err(err) => Some(Action::Error(format!("{err}"))),
+            },
+            Action::RenameProfile(uid, name) => match self.repo.rename(&uid, &name).await {
+                Ok(()) => Some(Action::UpdatePrfList(self.repo.list().await?)),
+                Err(err) => Some(Action::Error(format!("{err}"))),
+            },
+            Action::LoadProfile(uid) => match self.load_profile(&uid).await {
+                Ok(data) => Some(Action::ProfileData(uid, data)),
+                Err(err) => Some(Action::Error(format!("{err}"))),
+            },
+            Action::LoadRevisions(uid) => {
+                let Some(item) = self.repo.get(&uid).await? else {
+                    return Ok(());
+                };
+                // 第一项为当前内容
+                let current = PrfRevision {
+                    id: 0,
+                    updated: item.updated.unwrap_or(0),
+                    file_data: db::read_prf_file(&item).await.unwrap_or_default(),
+                };
+                let revisions = std::iter::once(current)
+                    .chain(self.repo.revisions(&uid).await?)
+                    .collect();
+                Some(Action::Revisions(uid, revisions))
+            }
+            Action::RollbackProfile(uid, id) => match self.repo.rollback(&uid, id).await {
+                Ok(item) => {
+                    self.set_info(format!(
+                        "已回滚: {}",
+                        item.name.as_deref().unwrap_or("config")
+                    ));
+                    self.reactivate(&item).await?;
+                    Some(Action::UpdatePrfList(self.repo.list().await?))
+                }
+                Err(err) => Some(Action::Error(format!("{err}"))),
+            },
+            Action::EditProfile(uid) => self.edit_profile(&uid).await?,
+            Action::LoadProxies => self.load_proxies().await?,
+            Action::SelectProxy(group, name) => {
+                let Some(controller) = self.controller().await? else {
+                    return Ok(());
+                };
+                match controller.select_proxy(&group, &name).await {
+                    Ok(()) => self.set_info(format!("{group} 已切换到 {name}")),
+                    Err(err) => self
+                        .action_tx
+                        .send(Action::Error(format!("切换节点失败: {err}")))?,
+                }
+                self.load_proxies().await?
+            }
+            Action::TestDelay(names) => {
+                self.test_delay(names).await?;
+                None
+            }
+            Action::DelayResult(name, delay) => Some(Action::DelayResult(name, delay)),
+            Action::WatchConnections => {
+                if self.connections_task.is_none() {
+                    let Some(controller) = self.controller().await? else {
+                        return Ok(());
+                    };
+                    let action_tx = self.action_tx.clone();
+                    self.connections_task =
+                        Some(tokio::spawn(Self::watch_connections(controller, action_tx)));
+                }
+                None
+            }
+            Action::ConnectionsData(data) => Some(Action::ConnectionsData(data)),
+            Action::CloseConnection(id) => {
+                let Some(controller) = self.controller().await? else {
+                    return Ok(());
                 };
-                Some(action)
+                match controller.close_connection(&id).await {
+                    Ok(()) => self.set_info("已关闭连接".to_string()),
+                    Err(err) => self
+                        .action_tx
+                        .send(Action::Error(format!("关闭连接失败: {err}")))?,
+                }
+                None
+            }
+            Action::CloseConnections => {
+                let Some(controller) = self.controller().await? else {
+                    return Ok(());
+                };
+                match controller.close_connections().await {
+                    Ok(()) => self.set_info("已关闭所有连接".to_string()),
+                    Err(err) => self
+                        .action_tx
+                        .send(Action::Error(format!("关闭连接失败: {err}")))?,
+                }
+                None
             }
             Action::SubScriptionUpdate => {
-                let items = db::query_prf_item().await?;
+                let items = self.repo.list().await?;
                 Some(Action::UpdatePrfList(items))
             }
+            Action::SubScriptionResult(item) => {
+                self.set_info(format!(
+                    "订阅已更新: {}",
+                    item.name.as_deref().unwrap_or("config")
+                ));
+                self.reactivate(&item).await?;
+                Some(Action::SubScriptionResult(item))
+            }
+            Action::UpdatePrfList(items) => Some(Action::UpdatePrfList(items)),
+            Action::ScriptError(uid, err) => Some(Action::ScriptError(uid, err)),
+            Action::Error(err) => {
+                self.set_info(err.clone());
+                Some(Action::Error(err))
+            }
+            Action::Warning(warning) => {
+                warn!("{warning}");
+                self.set_info(format!("警告: {warning}"));
+                None
+            }
             _ => None,
         };
+        // 处理结果中的错误同样显示在提示信息中
+        if let Some(Action::Error(err)) = &res_action {
+            self.set_info(err.clone());
+        }
         for menu in self.menus.iter_mut() {
             menu.update(res_action.clone())?;
         }
         Ok(())
     }
 
+    /// 激活配置: 生成运行时配置并写入内核的配置文件, 设为唯一选中的配置后通知内核重新加载
+    ///
+    /// 生成或写入失败时不改变选中的配置
+    async fn activate(&mut self, uid: &str) -> Result<Option<Action>> {
+        let core = self.config.config.core.clone();
+        let config = match enhance::generate(&self.repo, uid).await {
+            Ok(config) => enhance::use_overrides(&core, config),
+            Err(err) => {
+                return Ok(Some(match err.downcast::<ScriptError>() {
+                    Ok(err) => {
+                        self.set_info(err.message.clone());
+                        Action::ScriptError(err.uid, err.message)
+                    }
+                    Err(err) => Action::Error(format!("{err}")),
+                }))
+            }
+        };
+        // 先检查 external-controller, 出错时不写入配置也不改变选中的配置
+        let controller = match Controller::from_config(&config) {
+            Ok(controller) => controller,
+            Err(err) => {
+                self.action_tx.send(Action::Error(format!(
+                    "external-controller 配置错误: {err}"
+                )))?;
+                return Ok(None);
+            }
+        };
+        let path = core.config_path();
+        let payload = match enhance::write_runtime(&config, &path).await {
+            Ok(payload) => payload,
+            Err(err) => {
+                self.action_tx.send(Action::Error(format!(
+                    "写入内核配置 {} 失败: {err}",
+                    path.display()
+                )))?;
+                return Ok(None);
+            }
+        };
+        self.repo.select(uid).await?;
+        let name = self
+            .repo
+            .get(uid)
+            .await?
+            .and_then(|item| item.name)
+            .unwrap_or("config".to_string());
+        self.controller = controller;
+        // 连接信息的任务持有旧的 controller, 停止后由下一次 WatchConnections 重新建立
+        if let Some(task) = self.connections_task.take() {
+            task.abort();
+            if matches!(self.mode, Mode::Connections) {
+                self.action_tx.send(Action::WatchConnections)?;
+            }
+        }
+        let reload = match &self.controller {
+            Some(controller) => controller.reload(&payload).await,
+            None => Ok(()),
+        };
+        match reload {
+            Ok(()) => self.set_info(format!("已激活 {name}, 内核已重新加载配置")),
+            Err(err) if controller::is_connect_error(&err) => self.set_info(format!(
+                "已激活 {name}, 配置已写入 {}, 内核未运行",
+                path.display()
+            )),
+            Err(err) => self
+                .action_tx
+                .send(Action::Error(format!("内核重新加载配置失败: {err}")))?,
+        }
+        Ok(Some(Action::UpdatePrfList(self.repo.list().await?)))
+    }
+
+    /// 配置内容变化后重新激活选中的配置, 使内核使用最新的运行时配置
+    ///
+    /// 只在变化的是选中的配置, 或会叠加到选中配置上的 merge / script 配置时重新生成,
+    /// 结果通过 action_tx 发送
+    async fn reactivate(&mut self, changed: &PrfItem) -> Result<()> {
+        let selected = self
+            .repo
+            .list()
+            .await?
+            .into_iter()
+            .find(|item| item.selected == Some(true))
+            .and_then(|item| item.uid);
+        let Some(selected) = selected else {
+            return Ok(());
+        };
+        if changed.uid.as_ref() != Some(&selected) && !enhance::is_enhancer(changed) {
+            return Ok(());
+        }
+        debug!("重新激活配置: {selected}");
+        if let Some(action) = self.activate(&selected).await? {
+            self.action_tx.send(action)?;
+        }
+        Ok(())
+    }
+
+    /// 内核的 external-controller 客户端, 地址和 secret 从内核的配置文件读取
+    ///
+    /// 还没有激活过配置时使用全局配置中的设置
+    ///
+    /// 地址无效时通过 Action::Error 提示并返回 None
+    async fn controller(&mut self) -> Result<Option<Controller>> {
+        if let Some(controller) = &self.controller {
+            return Ok(Some(controller.clone()));
+        }
+        let core = &self.config.config.core;
+        let path = core.config_path();
+        let config = match tokio::fs::read_to_string(&path).await {
+            Ok(data) => enhance::parse_mapping(&data).unwrap_or_else(|err| {
+                warn!("无法解析内核配置 {}: {err}", path.display());
+                Mapping::new()
+            }),
+            Err(_) => Mapping::new(),
+        };
+        // use_overrides 会补上默认的 external-controller
+        let controller = Controller::from_config(&enhance::use_overrides(core, config))
+            .and_then(|controller| controller.ok_or_else(|| eyre!("没有配置 external-controller")));
+        match controller {
+            Ok(controller) => {
+                self.controller = Some(controller.clone());
+                Ok(Some(controller))
+            }
+            Err(err) => {
+                self.action_tx
+                    .send(Action::Error(format!("无法创建内核客户端: {err}")))?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// 从内核获取节点和策略组
+    async fn load_proxies(&mut self) -> Result<Option<Action>> {
+        let Some(controller) = self.controller().await? else {
+            return Ok(None);
+        };
+        let err = match controller.proxies().await {
+            Ok(proxies) => return Ok(Some(Action::ProxiesData(proxies))),
+            Err(err) if controller::is_connect_error(&err) => {
+                format!("无法连接内核, 请确认内核正在运行: {err}")
+            }
+            Err(err) => format!("获取节点失败: {err}"),
+        };
+        self.action_tx.send(Action::Error(err))?;
+        Ok(None)
+    }
+
+    /// 在后台并发测试节点的延迟, 每个节点测试完成后立即发送结果
+    async fn test_delay(&mut self, names: Vec<String>) -> Result<()> {
+        let Some(controller) = self.controller().await? else {
+            return Ok(());
+        };
+        let config = self.config.config.delay.clone();
+        let action_tx = self.action_tx.clone();
+        tokio::spawn(async move {
+            let url = config.url();
+            let timeout = config.timeout();
+            let mut results = stream::iter(names)
+                .map(|name| {
+                    let (controller, url) = (&controller, &url);
+                    async move {
+                        let delay = match controller.proxy_delay(&name, url, timeout).await {
+                            Ok(delay) => delay,
+                            Err(err) => {
+                                warn!("测试 {name} 的延迟失败: {err}");
+                                Delay::Failed
+                            }
+                        };
+                        (name, delay)
+                    }
+                })
+                .buffer_unordered(config.concurrency());
+            while let Some((name, delay)) = results.next().await {
+                if action_tx.send(Action::DelayResult(name, delay)).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(())
+    }
+
+    /// 接收内核的连接信息, 优先使用 WebSocket, 内核不支持或连接断开后改为每秒轮询
+    async fn watch_connections(controller: Controller, action_tx: UnboundedSender<Action>) {
+        let interval = Duration::from_secs(1);
+        match controller.watch_connections(interval).await {
+            Ok(mut connections) => {
+                while let Some(Ok(data)) = connections.next().await {
+                    if action_tx.send(Action::ConnectionsData(data)).is_err() {
+                        return;
+                    }
+                }
+                debug!("连接信息的 WebSocket 已断开, 改为轮询");
+            }
+            Err(err) => {
+                let warning = format!("无法通过 WebSocket 获取连接信息, 改为轮询: {err}");
+                if action_tx.send(Action::Warning(warning)).is_err() {
+                    return;
+                }
+            }
+        }
+        // 连续失败时只提示一次
+        let mut failed = false;
+        loop {
+            let action = match controller.connections().await {
+                Ok(data) => {
+                    failed = false;
+                    Some(Action::ConnectionsData(data))
+                }
+                Err(err) if !failed => {
+                    failed = true;
+                    Some(Action::Error(format!("获取连接失败: {err}")))
+                }
+                Err(_) => None,
+            };
+            if let Some(action) = action {
+                if action_tx.send(action).is_err() {
+                    return;
+                }
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    /// 读取订阅的配置文件内容
+    async fn load_profile(&self, uid: &str) -> Result<String> {
+        let item = self
+            .repo
+            .get(uid)
+            .await?
+            .ok_or_else(|| eyre!("订阅不存在: {uid}"))?;
+        db::read_prf_file(&item).await
+    }
+
+    /// 暂停界面并在外部编辑器中打开配置文件, 编辑器退出后恢复界面并校验修改后的内容
+    ///
+    /// 修改前的内容保存为历史版本, 校验失败时保留修改, 可以再次编辑或回滚
+    async fn edit_profile(&mut self, uid: &str) -> Result<Option<Action>> {
+        let Some(item) = self.repo.get(uid).await? else {
+            return Ok(Some(Action::Error(format!("订阅不存在: {uid}"))));
+        };
+        let Some(path) = db::prf_file_path(&item) else {
+            return Ok(Some(Action::Error("订阅文件名为空".to_string())));
+        };
+        let name = item.name.clone().unwrap_or("config".to_string());
+        let old = db::read_prf_file(&item).await.unwrap_or_default();
+        let (program, args) =
+            help::editor_command(std::env::var("VISUAL").ok(), std::env::var("EDITOR").ok());
+        debug!("编辑配置: {program} {args:?} {}", path.display());
+        let status = match self.exit() {
+            Ok(()) => tokio::process::Command::new(&program)
+                .args(&args)
+                .arg(&path)
+                .status()
+                .await
+                .map_err(|err| eyre!("无法启动编辑器 {program}: {err}, 请设置 $EDITOR")),
+            Err(err) => Err(eyre!("无法暂停界面: {err}")),
+        };
+        // 编辑器没有启动时也要恢复界面
+        self.enter()?;
+        self.redraw = true;
+        match status {
+            Ok(status) if status.success() => {}
+            Ok(status) => return Ok(Some(Action::Error(format!("编辑器异常退出: {status}")))),
+            Err(err) => return Ok(Some(Action::Error(format!("{err}")))),
+        }
+        let data = match tokio::fs::read_to_string(&path).await {
+            Ok(data) => data,
+            Err(err) => {
+                return Ok(Some(Action::Error(format!(
+                    "读取 {} 失败: {err}",
+                    path.display()
+                ))))
+            }
+        };
+        if data == old {
+            self.set_info(format!("{name} 未修改"));
+            return Ok(None);
+        }
+        let revision = PrfItem {
+            file_data: Some(old),
+            ..item.clone()
+        };
+        self.repo.insert_revision(&revision).await?;
+        // 更新修改时间, 订阅页面据此重新加载内容
+        let edited = PrfItem {
+            updated: Some(chrono::Utc::now().timestamp() as usize),
+            ..item.clone()
+        };
+        self.repo.update(&edited).await?;
+        let checked = match item.itype.as_deref() {
+            Some("merge") => enhance::parse_mapping(&data).map(|_| Vec::new()),
+            Some("script") => enhance::script::check_script(&data).map(|_| Vec::new()),
+            _ => clash::check_profile(&PrfItem {
+                file_data: Some(data),
+                ..item
+            }),
+        };
+        match checked {
+            Ok(warnings) => {
+                self.set_info(format!("已保存修改: {name}"));
+                for warning in warnings {
+                    self.action_tx.send(Action::Warning(warning))?;
+                }
+                self.reactivate(&edited).await?;
+            }
+            Err(err) => self.action_tx.send(Action::Error(format!(
+                "{name} 校验失败, 按 e 重新编辑或在历史版本中回滚: {err}"
+            )))?,
+        }
+        Ok(Some(Action::UpdatePrfList(self.repo.list().await?)))
+    }
+
+    /// 校验并保存新建的配置, 出错时返回 Action::Error, 校验警告通过 Action::Warning 发送
+    ///
+    /// 文件名和已有的配置重复时加上序号, 不会覆盖其他配置的文件
+    async fn create_profile(&self, item: anyhow::Result<PrfItem>) -> Result<Action> {
+        let item = match item {
+            Ok(item) => item,
+            Err(err) => return Ok(Action::Error(format!("{err:?}"))),
+        };
+        match clash::check_profile(&item) {
+            Ok(warnings) => {
+                for warning in warnings {
+                    self.action_tx.send(Action::Warning(warning))?;
+                }
+            }
+            Err(err) => return Ok(Action::Error(format!("{err}"))),
+        }
+        Ok(Action::SubScriptionResult(self.repo.create(item).await?))
+    }
+
     fn current_menus(&mut self) -> &mut Box<dyn View> {
         &mut self.menus[self.menu_index as usize]
     }
 
     fn handle_events(&mut self) -> Result<()> {
+        // 没有输入时也要定期返回, 以便处理后台事件
+        if !event::poll(Duration::from_millis(250))? {
+            return Ok(());
+        }
         let mut event = Some(event::read()?);
         for menu in self.menus.iter_mut() {
             if menu.is_focus() {
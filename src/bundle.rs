@@ -0,0 +1,385 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{Read, Write},
+    path::Path,
+};
+
+use chrono::Utc;
+use clap::ValueEnum;
+use color_eyre::{eyre::eyre, Result};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::{
+    config::{get_config_dir, get_subscribe_dir},
+    db::{self, PrfRepo},
+    prfitem::PrfItem,
+};
+
+/// 导出文件的格式版本
+const BUNDLE_VERSION: u32 = 1;
+const MANIFEST: &str = "manifest.json";
+const SUBSCRIBE_DIR: &str = "subscribe";
+const CONFIG_DIR: &str = "config";
+
+/// 导出文件中所有订阅的信息
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    version: u32,
+    app_version: String,
+    created: i64,
+    profiles: Vec<PrfItem>,
+}
+
+/// 解包后的导出文件
+#[derive(Debug)]
+struct Bundle {
+    manifest: Manifest,
+    /// subscribe 目录中的文件 (文件名, 内容)
+    subscribe: Vec<(String, Vec<u8>)>,
+    /// 配置目录中的文件 (文件名, 内容)
+    config: Vec<(String, Vec<u8>)>,
+}
+
+/// 导入时如何处理已有的数据
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ImportMode {
+    /// Keep existing profiles and config files, profiles with the same uid are overwritten
+    #[default]
+    Merge,
+    /// Delete all existing profiles and overwrite the config files
+    Replace,
+}
+
+/// 导入的结果
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub imported: usize,
+    /// 跳过的订阅或文件及原因
+    pub skipped: Vec<String>,
+}
+
+/// 导出所有订阅, 订阅文件和配置文件到一个 tar.gz 文件, 返回导出的订阅数量
+pub async fn export(repo: &PrfRepo, path: &Path) -> Result<usize> {
+    let profiles = repo.list().await?;
+    let mut subscribe = Vec::new();
+    for item in &profiles {
+        let Some(file) = db::prf_file_path(item) else {
+            continue;
+        };
+        match tokio::fs::read(&file).await {
+            Ok(data) => subscribe.push((file_name(&file), data)),
+            Err(err) => warn!("跳过无法读取的订阅文件 {}: {err}", file.display()),
+        }
+    }
+    let config = read_dir_files(&get_config_dir())?;
+    let count = profiles.len();
+    let bundle = Bundle {
+        manifest: Manifest {
+            version: BUNDLE_VERSION,
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            created: Utc::now().timestamp(),
+            profiles,
+        },
+        subscribe,
+        config,
+    };
+    let file = File::create(path).map_err(|err| eyre!("无法创建 {}: {err}", path.display()))?;
+    write_bundle(file, &bundle)?;
+    Ok(count)
+}
+
+/// 从 export 生成的文件中恢复订阅和配置文件
+///
+/// 订阅文件先写入临时目录, 订阅在一个事务中保存后才替换已有的文件,
+/// 中途出错时不会修改已有的订阅. 导入的订阅都不是选中的配置
+pub async fn import(repo: &PrfRepo, path: &Path, mode: ImportMode) -> Result<ImportReport> {
+    let file = File::open(path).map_err(|err| eyre!("无法打开 {}: {err}", path.display()))?;
+    let bundle = read_bundle(file)?;
+    let files: HashMap<String, Vec<u8>> = bundle.subscribe.into_iter().collect();
+    let replace = mode == ImportMode::Replace;
+    let old = repo.list().await?;
+    // 替换时已有的订阅都会被删除, 不需要避开它们的文件名
+    let mut existing = if replace { Vec::new() } else { old.clone() };
+    let mut report = ImportReport::default();
+    let mut items = Vec::new();
+    for mut item in bundle.manifest.profiles {
+        let Some(uid) = item.uid.clone() else {
+            continue;
+        };
+        let name = item.name.clone().unwrap_or("config".to_string());
+        // 链接相同的订阅已存在时保留已有的, 避免重复订阅
+        if item.url.is_some()
+            && existing
+                .iter()
+                .any(|e| e.url == item.url && e.uid.as_ref() != Some(&uid))
+        {
+            report.skipped.push(format!("{name}: 订阅链接已存在"));
+            continue;
+        }
+        let Some(data) = item
+            .file
+            .as_ref()
+            .and_then(|file| files.get(&format!("{file}.yaml")))
+        else {
+            report.skipped.push(format!("{name}: 缺少配置文件"));
+            continue;
+        };
+        let file = db::file_name(item.file.as_deref().unwrap_or_default());
+        item.file = Some(match replace {
+            // 已有订阅的文件会被删除, 只需要避开没有订阅使用的文件
+            true => db::numbered_file(&file, |name| {
+                let owned =
+                    |items: &[PrfItem]| items.iter().any(|e| e.file.as_deref() == Some(name));
+                owned(&existing)
+                    || (!owned(&old) && get_subscribe_dir().join(format!("{name}.yaml")).exists())
+            }),
+            false => db::unique_file(&existing, &uid, &file),
+        });
+        item.selected = Some(false);
+        item.file_data = Some(String::from_utf8_lossy(data).to_string());
+        existing.retain(|e| e.uid.as_ref() != Some(&uid));
+        existing.push(item.clone());
+        items.push(item);
+    }
+
+    let staging = get_subscribe_dir().join(format!(".import-{}", uuid::Uuid::new_v4()));
+    let saved = async {
+        stage_files(&staging, &items).await?;
+        repo.save_all(&items, replace).await
+    };
+    if let Err(err) = saved.await {
+        let _ = tokio::fs::remove_dir_all(&staging).await;
+        return Err(err);
+    }
+    // 删除被替换的订阅, 或 uid 相同但改用了其他文件名的订阅的旧文件
+    for item in &old {
+        let uid = item.uid.as_ref();
+        let kept = items
+            .iter()
+            .find(|i| i.uid.as_ref() == uid)
+            .map_or(!replace, |i| i.file == item.file);
+        let Some(old_path) = db::prf_file_path(item).filter(|_| !kept) else {
+            continue;
+        };
+        if old_path.exists() && !items.iter().any(|i| i.file == item.file) {
+            tokio::fs::remove_file(old_path).await?;
+        }
+    }
+    for item in &items {
+        let file = format!("{}.yaml", item.file.as_deref().unwrap_or_default());
+        debug!("导入订阅: {file}");
+        tokio::fs::rename(staging.join(&file), get_subscribe_dir().join(&file)).await?;
+    }
+    tokio::fs::remove_dir_all(&staging).await?;
+    report.imported = items.len();
+
+    let config_dir = get_config_dir();
+    if !bundle.config.is_empty() {
+        std::fs::create_dir_all(&config_dir)?;
+    }
+    for (name, data) in bundle.config {
+        let path = config_dir.join(&name);
+        if mode == ImportMode::Merge && path.exists() {
+            report.skipped.push(format!("配置文件 {name} 已存在"));
+            continue;
+        }
+        std::fs::write(path, data)?;
+    }
+    Ok(report)
+}
+
+/// 将订阅内容写入临时目录
+async fn stage_files(staging: &Path, items: &[PrfItem]) -> Result<()> {
+    tokio::fs::create_dir_all(staging).await?;
+    for item in items {
+        let file = format!("{}.yaml", item.file.as_deref().unwrap_or_default());
+        let data = item.file_data.as_deref().unwrap_or_default();
+        tokio::fs::write(staging.join(file), data).await?;
+    }
+    Ok(())
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// 读取目录下的文件, 不包括子目录, 目录不存在时返回空
+fn read_dir_files(dir: &Path) -> Result<Vec<(String, Vec<u8>)>> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() {
+            files.push((file_name(&path), std::fs::read(&path)?));
+        }
+    }
+    Ok(files)
+}
+
+fn write_bundle<W: Write>(writer: W, bundle: &Bundle) -> Result<()> {
+    let mut tar = tar::Builder::new(GzEncoder::new(writer, Compression::default()));
+    let manifest = serde_json::to_vec_pretty(&bundle.manifest)?;
+    append(&mut tar, MANIFEST, &manifest)?;
+    for (name, data) in &bundle.subscribe {
+        append(&mut tar, &format!("{SUBSCRIBE_DIR}/{name}"), data)?;
+    }
+    for (name, data) in &bundle.config {
+        append(&mut tar, &format!("{CONFIG_DIR}/{name}"), data)?;
+    }
+    tar.into_inner()?.finish()?;
+    Ok(())
+}
+
+fn append<W: Write>(tar: &mut tar::Builder<W>, path: &str, data: &[u8]) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(Utc::now().timestamp() as u64);
+    header.set_cksum();
+    tar.append_data(&mut header, path, data)?;
+    Ok(())
+}
+
+fn read_bundle<R: Read>(reader: R) -> Result<Bundle> {
+    let mut archive = tar::Archive::new(GzDecoder::new(reader));
+    let mut manifest = None;
+    let (mut subscribe, mut config) = (Vec::new(), Vec::new());
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry.path()?.to_string_lossy().to_string();
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data)?;
+        // 只接受固定的目录结构, 文件名中不能包含路径
+        let parts: Vec<&str> = path.split('/').collect();
+        match parts.as_slice() {
+            [MANIFEST] => manifest = Some(serde_json::from_slice::<Manifest>(&data)?),
+            [SUBSCRIBE_DIR, name] if is_file_name(name) => subscribe.push((name.to_string(), data)),
+            [CONFIG_DIR, name] if is_file_name(name) => config.push((name.to_string(), data)),
+            _ => warn!("忽略导出文件中的 {path}"),
+        }
+    }
+    let manifest = manifest.ok_or_else(|| eyre!("导出文件中没有 {MANIFEST}"))?;
+    if manifest.version > BUNDLE_VERSION {
+        return Err(eyre!(
+            "导出文件版本 {} 高于支持的版本 {BUNDLE_VERSION}, 请升级 lazyclash",
+            manifest.version
+        ));
+    }
+    Ok(Bundle {
+        manifest,
+        subscribe,
+        config,
+    })
+}
+
+fn is_file_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains('\\')
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn profile(uid: &str, file: &str) -> PrfItem {
+        PrfItem {
+            uid: Some(uid.to_string()),
+            name: Some(file.to_string()),
+            file: Some(file.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn bundle_roundtrip() -> Result<()> {
+        let bundle = Bundle {
+            manifest: Manifest {
+                version: BUNDLE_VERSION,
+                app_version: "0.1.0".to_string(),
+                created: 0,
+                profiles: vec![profile("a", "work")],
+            },
+            subscribe: vec![("work.yaml".to_string(), b"proxies: []\n".to_vec())],
+            config: vec![("config.json5".to_string(), b"{}".to_vec())],
+        };
+        let mut data = Vec::new();
+        write_bundle(&mut data, &bundle)?;
+        let bundle = read_bundle(data.as_slice())?;
+        assert_eq!(bundle.manifest.profiles[0].uid.as_deref(), Some("a"));
+        assert_eq!(
+            bundle.subscribe,
+            vec![("work.yaml".to_string(), b"proxies: []\n".to_vec())]
+        );
+        assert_eq!(bundle.config[0].0, "config.json5");
+        Ok(())
+    }
+
+    #[test]
+    fn reject_newer_bundle() -> Result<()> {
+        let bundle = Bundle {
+            manifest: Manifest {
+                version: BUNDLE_VERSION + 1,
+                app_version: "9.9.9".to_string(),
+                created: 0,
+                profiles: Vec::new(),
+            },
+            subscribe: Vec::new(),
+            config: Vec::new(),
+        };
+        let mut data = Vec::new();
+        write_bundle(&mut data, &bundle)?;
+        assert!(read_bundle(data.as_slice()).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn import_replace() -> Result<()> {
+        let repo = db::memory_repo().await?;
+        let old = repo
+            .create(PrfItem {
+                uid: Some("old".to_string()),
+                file: Some("replaced".to_string()),
+                file_data: Some("proxies: []\n".to_string()),
+                ..Default::default()
+            })
+            .await?;
+        repo.select("old").await?;
+        let mut imported = profile("new", "imported");
+        imported.selected = Some(true);
+        let bundle = Bundle {
+            manifest: Manifest {
+                version: BUNDLE_VERSION,
+                app_version: "0.1.0".to_string(),
+                created: 0,
+                profiles: vec![imported],
+            },
+            subscribe: vec![("imported.yaml".to_string(), b"rules: []\n".to_vec())],
+            config: Vec::new(),
+        };
+        let path = get_subscribe_dir().join("import_replace.tar.gz");
+        std::fs::create_dir_all(get_subscribe_dir())?;
+        write_bundle(File::create(&path)?, &bundle)?;
+
+        let report = import(&repo, &path, ImportMode::Replace).await?;
+        assert_eq!(report.imported, 1);
+        let items = repo.list().await?;
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].uid.as_deref(), Some("new"));
+        // 导入的订阅不会被选中
+        assert_eq!(items[0].selected, Some(false));
+        assert_eq!(db::read_prf_file(&items[0]).await?, "rules: []\n");
+        assert!(!db::prf_file_path(&old).unwrap().exists());
+        repo.delete("new").await?;
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,516 @@
+use std::collections::{HashMap, HashSet};
+
+use color_eyre::{eyre::eyre, Result};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_yaml::{Mapping, Value};
+
+use crate::prfitem::PrfItem;
+
+pub mod controller;
+pub mod convert;
+
+/// clash/mihomo 配置, 未建模的字段保留在 `extra` 中
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ClashConfig {
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "port"
+    )]
+    pub port: Option<u16>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "port"
+    )]
+    pub socks_port: Option<u16>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "port"
+    )]
+    pub mixed_port: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allow_lan: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_level: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub external_controller: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub proxies: Vec<Proxy>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub proxy_groups: Vec<ProxyGroup>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub sub_rules: HashMap<String, Vec<String>>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub proxy_providers: HashMap<String, Provider>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub rule_providers: HashMap<String, Provider>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dns: Option<Dns>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tun: Option<Tun>,
+    #[serde(flatten)]
+    pub extra: Mapping,
+}
+
+/// 代理节点
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Proxy {
+    #[serde(deserialize_with = "name")]
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ptype: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server: Option<String>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "port"
+    )]
+    pub port: Option<u16>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "boolean"
+    )]
+    pub udp: Option<bool>,
+    #[serde(flatten)]
+    pub extra: Mapping,
+}
+
+/// 代理组
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ProxyGroup {
+    #[serde(deserialize_with = "name")]
+    pub name: String,
+    #[serde(rename = "type")]
+    pub gtype: String,
+    #[serde(
+        default,
+        skip_serializing_if = "Vec::is_empty",
+        deserialize_with = "names"
+    )]
+    pub proxies: Vec<String>,
+    #[serde(default, rename = "use", skip_serializing_if = "Vec::is_empty")]
+    pub use_providers: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "number"
+    )]
+    pub interval: Option<u64>,
+    #[serde(flatten)]
+    pub extra: Mapping,
+}
+
+/// proxy-providers 和 rule-providers
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Provider {
+    #[serde(rename = "type")]
+    pub ptype: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "number"
+    )]
+    pub interval: Option<u64>,
+    #[serde(flatten)]
+    pub extra: Mapping,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Dns {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enable: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub listen: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enhanced_mode: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub nameserver: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fallback: Vec<String>,
+    #[serde(flatten)]
+    pub extra: Mapping,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Tun {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enable: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stack: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auto_route: Option<bool>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dns_hijack: Vec<String>,
+    #[serde(flatten)]
+    pub extra: Mapping,
+}
+
+/// 数字也可能写成字符串, 如 `interval: "300"`
+fn number<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<Value>::deserialize(deserializer)? {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::Number(n)) => n
+            .as_u64()
+            .map(Some)
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid number {n}"))),
+        Some(Value::String(s)) => s
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|_| serde::de::Error::custom(format!("invalid number {s}"))),
+        Some(other) => Err(serde::de::Error::custom(format!(
+            "invalid number {other:?}"
+        ))),
+    }
+}
+
+/// 布尔值也可能写成字符串, 如 `udp: "true"`
+fn boolean<'de, D>(deserializer: D) -> Result<Option<bool>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<Value>::deserialize(deserializer)? {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::Bool(b)) => Ok(Some(b)),
+        Some(Value::String(s)) => match s.trim().to_lowercase().as_str() {
+            "true" => Ok(Some(true)),
+            "false" => Ok(Some(false)),
+            _ => Err(serde::de::Error::custom(format!("invalid bool {s}"))),
+        },
+        Some(other) => Err(serde::de::Error::custom(format!("invalid bool {other:?}"))),
+    }
+}
+
+/// 端口既可能是数字也可能是字符串
+fn port<'de, D>(deserializer: D) -> Result<Option<u16>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<Value>::deserialize(deserializer)? {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::Number(n)) => n
+            .as_u64()
+            .and_then(|n| u16::try_from(n).ok())
+            .map(Some)
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid port {n}"))),
+        Some(Value::String(s)) => s
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|_| serde::de::Error::custom(format!("invalid port {s}"))),
+        Some(other) => Err(serde::de::Error::custom(format!("invalid port {other:?}"))),
+    }
+}
+
+/// 节点和代理组的名称, 如 `name: 2024` 会被 yaml 解析为数字
+fn name<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    scalar_string(Value::deserialize(deserializer)?)
+}
+
+fn names<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<Vec<Value>>::deserialize(deserializer)?
+        .unwrap_or_default()
+        .into_iter()
+        .map(scalar_string)
+        .collect()
+}
+
+fn scalar_string<E: serde::de::Error>(value: Value) -> Result<String, E> {
+    match value {
+        Value::String(s) => Ok(s),
+        Value::Number(n) => Ok(n.to_string()),
+        Value::Bool(b) => Ok(b.to_string()),
+        other => Err(E::custom(format!("invalid name {other:?}"))),
+    }
+}
+
+/// clash 内置的策略
+const BUILTIN_POLICIES: [&str; 4] = ["DIRECT", "REJECT", "REJECT-DROP", "PASS"];
+
+impl ClashConfig {
+    /// 解析配置, 无法作为配置使用的内容 (如错误页, 登录页) 直接返回错误
+    pub fn parse(data: &str) -> Result<Self> {
+        let head = data.trim_start().get(..64).unwrap_or(data.trim_start());
+        let head = head.to_ascii_lowercase();
+        if head.starts_with("<!doctype") || head.starts_with("<html") {
+            return Err(eyre!("内容是 HTML 页面, 不是 clash 配置"));
+        }
+        let value: Value =
+            serde_yaml::from_str(data).map_err(|err| eyre!("YAML 解析失败: {err}"))?;
+        if !value.is_mapping() {
+            return Err(eyre!("配置文件不是一个映射"));
+        }
+        let config: ClashConfig =
+            serde_yaml::from_value(value).map_err(|err| eyre!("配置格式错误: {err}"))?;
+        if config.proxies.is_empty()
+            && config.proxy_providers.is_empty()
+            && config.proxy_groups.is_empty()
+            && config.rules.is_empty()
+        {
+            return Err(eyre!("配置中没有代理节点, 代理组或规则"));
+        }
+        Ok(config)
+    }
+
+    /// 检查配置中的引用关系, 返回警告信息
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        let mut names: HashSet<&str> = BUILTIN_POLICIES.into_iter().collect();
+        for proxy in &self.proxies {
+            if !names.insert(&proxy.name) {
+                warnings.push(format!("节点名称重复: {}", proxy.name));
+            }
+        }
+        for group in &self.proxy_groups {
+            if !names.insert(&group.name) {
+                warnings.push(format!("代理组名称重复: {}", group.name));
+            }
+        }
+        for group in &self.proxy_groups {
+            for proxy in &group.proxies {
+                if !names.contains(proxy.as_str()) {
+                    warnings.push(format!("代理组 {} 引用了不存在的节点 {proxy}", group.name));
+                }
+            }
+            for provider in &group.use_providers {
+                if !self.proxy_providers.contains_key(provider) {
+                    warnings.push(format!(
+                        "代理组 {} 引用了不存在的 provider {provider}",
+                        group.name
+                    ));
+                }
+            }
+        }
+        for rule in self.rules.iter().chain(self.sub_rules.values().flatten()) {
+            match rule_target(rule) {
+                Some(RuleTarget::Policy(target)) if !names.contains(target) => {
+                    warnings.push(format!("规则 {rule} 引用了不存在的策略 {target}"));
+                }
+                Some(RuleTarget::SubRule(target)) if !self.sub_rules.contains_key(target) => {
+                    warnings.push(format!("规则 {rule} 引用了不存在的子规则 {target}"));
+                }
+                _ => {}
+            }
+        }
+        if self.rules.is_empty() {
+            warnings.push("配置中没有规则".to_string());
+        }
+        warnings
+    }
+}
+
+/// 规则引用的目标
+#[derive(Debug, PartialEq)]
+enum RuleTarget<'a> {
+    /// 节点, 代理组或内置策略
+    Policy(&'a str),
+    /// `sub-rules` 中的子规则
+    SubRule(&'a str),
+}
+
+/// 规则的目标, 如 `DOMAIN,example.com,PROXY,no-resolve` => `PROXY`
+///
+/// 逻辑规则和 `SUB-RULE` 的条件写在括号中, 其中的逗号不分隔字段
+fn rule_target(rule: &str) -> Option<RuleTarget<'_>> {
+    let (rtype, rest) = rule.split_once(',')?;
+    let rtype = rtype.trim();
+    let rest = rest.trim_start();
+    let rest = match rtype {
+        "MATCH" => rest,
+        "AND" | "OR" | "NOT" | "SUB-RULE" => {
+            let end = closing_paren(rest)?;
+            rest[end + 1..].trim_start().strip_prefix(',')?
+        }
+        _ => rest.split_once(',')?.1,
+    };
+    let target = rest.split(',').next()?.trim();
+    if target.is_empty() {
+        return None;
+    }
+    Some(match rtype {
+        "SUB-RULE" => RuleTarget::SubRule(target),
+        _ => RuleTarget::Policy(target),
+    })
+}
+
+/// 与开头的 `(` 配对的 `)` 的位置
+fn closing_paren(text: &str) -> Option<usize> {
+    if !text.starts_with('(') {
+        return None;
+    }
+    let mut depth = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// 校验订阅内容, merge 和 script 配置不是完整的 clash 配置, 不做校验
+pub fn check_profile(item: &PrfItem) -> Result<Vec<String>> {
+    if matches!(item.itype.as_deref(), Some("merge") | Some("script")) {
+        return Ok(Vec::new());
+    }
+    let config = ClashConfig::parse(item.file_data.as_deref().unwrap_or_default())?;
+    Ok(config.warnings())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const PROFILE: &str = r#"
+mixed-port: "7890"
+allow-lan: false
+external-controller: 127.0.0.1:9090
+proxies:
+  - name: HK-01
+    type: ss
+    server: hk.example.com
+    port: 443
+    cipher: aes-128-gcm
+    password: secret
+    udp: "true"
+proxy-groups:
+  - name: PROXY
+    type: select
+    proxies: [HK-01, DIRECT, JP-01]
+  - name: AUTO
+    type: url-test
+    use: [sub]
+    url: http://www.gstatic.com/generate_204
+    interval: "300"
+proxy-providers:
+  sub:
+    type: http
+    url: https://example.com/sub
+    interval: "3600"
+rules:
+  - DOMAIN-SUFFIX,example.com,PROXY
+  - IP-CIDR,10.0.0.0/8,DIRECT,no-resolve
+  - AND,((DOMAIN,baidu.com),(NETWORK,UDP)),DIRECT
+  - SUB-RULE,(NETWORK,tcp),sub
+  - SUB-RULE,(NETWORK,udp),missing
+  - MATCH,FINAL
+sub-rules:
+  sub:
+    - DOMAIN,a.example.com,HK-01
+    - MATCH,REJECT
+dns:
+  enable: true
+  nameserver: [223.5.5.5]
+tun:
+  enable: false
+profile:
+  store-selected: true
+"#;
+
+    #[test]
+    fn parse_profile() -> Result<()> {
+        let config = ClashConfig::parse(PROFILE)?;
+        assert_eq!(config.mixed_port, Some(7890));
+        assert_eq!(config.proxies[0].port, Some(443));
+        assert_eq!(
+            config.proxies[0].extra.get("cipher"),
+            Some(&"aes-128-gcm".into())
+        );
+        assert_eq!(config.proxies[0].udp, Some(true));
+        assert_eq!(config.proxy_groups[0].proxies.len(), 3);
+        assert_eq!(config.proxy_groups[1].interval, Some(300));
+        assert_eq!(config.proxy_providers["sub"].interval, Some(3600));
+        assert!(config.extra.contains_key("profile"));
+        assert_eq!(
+            config.warnings(),
+            vec![
+                "代理组 PROXY 引用了不存在的节点 JP-01".to_string(),
+                "规则 SUB-RULE,(NETWORK,udp),missing 引用了不存在的子规则 missing".to_string(),
+                "规则 MATCH,FINAL 引用了不存在的策略 FINAL".to_string(),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn rule_targets() {
+        let policy = |rule| rule_target(rule).unwrap();
+        assert_eq!(
+            policy("DOMAIN,example.com,PROXY,no-resolve"),
+            RuleTarget::Policy("PROXY")
+        );
+        assert_eq!(policy("MATCH,DIRECT"), RuleTarget::Policy("DIRECT"));
+        assert_eq!(
+            policy("AND,((DOMAIN,baidu.com),(NETWORK,UDP)),DIRECT"),
+            RuleTarget::Policy("DIRECT")
+        );
+        assert_eq!(
+            policy("OR,((NETWORK,UDP),(DST-PORT,443)),REJECT,src"),
+            RuleTarget::Policy("REJECT")
+        );
+        assert_eq!(
+            policy("NOT,((DOMAIN,baidu.com)),PROXY"),
+            RuleTarget::Policy("PROXY")
+        );
+        assert_eq!(
+            policy("SUB-RULE,(NETWORK,tcp),sub"),
+            RuleTarget::SubRule("sub")
+        );
+        assert_eq!(rule_target("AND,((DOMAIN,baidu.com),DIRECT"), None);
+        assert_eq!(rule_target("DOMAIN,example.com"), None);
+    }
+
+    #[test]
+    fn numeric_names() -> Result<()> {
+        let config = ClashConfig::parse(
+            "proxies:\n  - {name: 2024, type: ss, server: a.com, port: 443}\nproxy-groups:\n  - {name: 1, type: select, proxies: [2024, DIRECT]}\nrules:\n  - MATCH,1\n",
+        )?;
+        assert_eq!(config.proxies[0].name, "2024");
+        assert_eq!(config.proxy_groups[0].name, "1");
+        assert_eq!(config.proxy_groups[0].proxies, ["2024", "DIRECT"]);
+        assert!(config.warnings().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn reject_invalid() {
+        assert!(ClashConfig::parse("<!DOCTYPE html><html><body>login</body></html>").is_err());
+        assert!(ClashConfig::parse("proxies: [").is_err());
+        assert!(ClashConfig::parse("just a string").is_err());
+        assert!(ClashConfig::parse("mixed-port: 7890").is_err());
+    }
+}
@@ -0,0 +1,774 @@
+#![allow(dead_code)] // 部分接口还没有界面使用
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use color_eyre::{eyre::eyre, Result};
+use futures::{stream::BoxStream, Stream, StreamExt};
+use reqwest::{Client, Method, RequestBuilder, Response, StatusCode, Url};
+use serde::de::DeserializeOwned;
+use serde_json::json;
+use serde_yaml::{Mapping, Value};
+use tokio_tungstenite::tungstenite::{
+    self, client::IntoClientRequest, http::header::AUTHORIZATION, Message,
+};
+
+pub use model::*;
+
+mod model;
+
+/// 请求内核的超时时间
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// clash/mihomo external-controller 的客户端
+#[derive(Debug, Clone)]
+pub struct Controller {
+    base: Url,
+    secret: Option<String>,
+    client: Client,
+    /// 通过 Unix socket 连接时的路径, WebSocket 连接不经过 client, 需要单独处理
+    unix: Option<PathBuf>,
+}
+
+impl Controller {
+    /// `addr` 为配置中 external-controller 的值, 如 `127.0.0.1:9090`, `:9090` 或 `http://host:9090`
+    pub fn new(addr: &str, secret: Option<String>) -> Result<Self> {
+        let base = Url::parse(&base_url(addr))
+            .map_err(|err| eyre!("external-controller 地址无效 {addr}: {err}"))?;
+        // 控制器在本机, 不能走系统代理
+        let client = Client::builder().no_proxy().timeout(TIMEOUT).build()?;
+        Ok(Self::with_client(client, base, secret))
+    }
+
+    /// 通过 external-controller-unix 指定的 Unix socket 连接内核
+    #[cfg(unix)]
+    pub fn unix(path: impl AsRef<Path>, secret: Option<String>) -> Result<Self> {
+        let client = Client::builder()
+            .unix_socket(path.as_ref())
+            .timeout(TIMEOUT)
+            .build()?;
+        // 走 Unix socket 时不解析域名, 主机名只用于 Host 请求头
+        let base = Url::parse("http://localhost")?;
+        Ok(Self {
+            unix: Some(path.as_ref().to_path_buf()),
+            ..Self::with_client(client, base, secret)
+        })
+    }
+
+    fn with_client(client: Client, base: Url, secret: Option<String>) -> Self {
+        Self {
+            base,
+            secret: secret.filter(|s| !s.is_empty()),
+            client,
+            unix: None,
+        }
+    }
+
+    /// 从运行时配置中读取控制器地址和 secret, 没有配置控制器时返回 None
+    ///
+    /// 同时配置了 external-controller-unix 时优先使用 Unix socket
+    pub fn from_config(config: &Mapping) -> Result<Option<Self>> {
+        let get = |key: &str| {
+            config
+                .get(key)
+                .and_then(Value::as_str)
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+        };
+        let secret = get("secret").map(str::to_string);
+        #[cfg(unix)]
+        if let Some(path) = get("external-controller-unix") {
+            return Ok(Some(Self::unix(path, secret)?));
+        }
+        match get("external-controller") {
+            Some(addr) => Ok(Some(Self::new(addr, secret)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// 拼接请求地址, 每一段都会转义, 节点名中可以有空格, `/` 或 emoji
+    fn url(&self, segments: &[&str]) -> Url {
+        let mut url = self.base.clone();
+        if let Ok(mut path) = url.path_segments_mut() {
+            path.pop_if_empty().extend(segments);
+        }
+        url
+    }
+
+    fn request(&self, method: Method, segments: &[&str]) -> RequestBuilder {
+        let req = self.client.request(method, self.url(segments));
+        match &self.secret {
+            Some(secret) => req.bearer_auth(secret),
+            None => req,
+        }
+    }
+
+    /// 发送请求, 非 2xx 响应时返回内核给出的错误信息
+    async fn send(req: RequestBuilder) -> Result<Response> {
+        Self::check(req.send().await?).await
+    }
+
+    async fn check(res: Response) -> Result<Response> {
+        let status = res.status();
+        if status.is_success() {
+            return Ok(res);
+        }
+        let body = res.text().await.unwrap_or_default();
+        let message = serde_json::from_str::<serde_json::Value>(&body)
+            .ok()
+            .and_then(|v| {
+                v.get("message")
+                    .and_then(|m| m.as_str())
+                    .map(str::to_string)
+            })
+            .unwrap_or(body);
+        Err(eyre!("内核返回 {status}: {message}"))
+    }
+
+    async fn get<T: DeserializeOwned>(&self, segments: &[&str]) -> Result<T> {
+        let res = Self::send(self.request(Method::GET, segments)).await?;
+        Ok(res.json().await?)
+    }
+
+    pub async fn version(&self) -> Result<Version> {
+        self.get(&["version"]).await
+    }
+
+    pub async fn configs(&self) -> Result<Configs> {
+        self.get(&["configs"]).await
+    }
+
+    /// 修改内核的部分配置, 如代理模式, 不会写回配置文件
+    pub async fn patch_configs(&self, patch: &ConfigsPatch) -> Result<()> {
+        Self::send(self.request(Method::PATCH, &["configs"]).json(patch)).await?;
+        Ok(())
+    }
+
+    /// 用新的配置内容替换内核当前的配置
+    ///
+    /// 直接发送配置内容而不是文件路径, mihomo 只允许加载其工作目录下的文件
+    pub async fn reload(&self, payload: &str) -> Result<()> {
+        let req = self
+            .request(Method::PUT, &["configs"])
+            .query(&[("force", "true")])
+            .json(&json!({ "path": "", "payload": payload }));
+        Self::send(req).await?;
+        Ok(())
+    }
+
+    /// 所有节点和策略组, 以名称为键
+    pub async fn proxies(&self) -> Result<HashMap<String, ProxyInfo>> {
+        let proxies: Proxies = self.get(&["proxies"]).await?;
+        Ok(proxies.proxies)
+    }
+
+    pub async fn proxy(&self, name: &str) -> Result<ProxyInfo> {
+        self.get(&["proxies", name]).await
+    }
+
+    /// 切换策略组选中的节点, 只有 select 类型的策略组可以切换
+    pub async fn select_proxy(&self, group: &str, name: &str) -> Result<()> {
+        let req = self
+            .request(Method::PUT, &["proxies", group])
+            .json(&json!({ "name": name }));
+        Self::send(req).await?;
+        Ok(())
+    }
+
+    /// 测试节点的延迟, 超时和节点不可用不算作请求出错
+    pub async fn proxy_delay(&self, name: &str, url: &str, timeout: Duration) -> Result<Delay> {
+        let req = self
+            .request(Method::GET, &["proxies", name, "delay"])
+            .query(&delay_query(url, timeout))
+            .timeout(timeout + TIMEOUT);
+        let res = req.send().await?;
+        // mihomo 超时返回 504, 测试失败返回 503
+        match res.status() {
+            StatusCode::GATEWAY_TIMEOUT | StatusCode::REQUEST_TIMEOUT => return Ok(Delay::Timeout),
+            StatusCode::SERVICE_UNAVAILABLE => return Ok(Delay::Failed),
+            _ => {}
+        }
+        let res: DelayResult = Self::check(res).await?.json().await?;
+        Ok(Delay::Ms(res.delay))
+    }
+
+    /// 测试策略组所有成员的延迟, 结果中没有测试失败或超时的节点
+    pub async fn group_delay(
+        &self,
+        group: &str,
+        url: &str,
+        timeout: Duration,
+    ) -> Result<HashMap<String, u16>> {
+        let req = self
+            .request(Method::GET, &["group", group, "delay"])
+            .query(&delay_query(url, timeout))
+            .timeout(timeout + TIMEOUT);
+        Ok(Self::send(req).await?.json().await?)
+    }
+
+    pub async fn proxy_providers(&self) -> Result<HashMap<String, ProxyProvider>> {
+        let providers: Providers<ProxyProvider> = self.get(&["providers", "proxies"]).await?;
+        Ok(providers.providers)
+    }
+
+    pub async fn proxy_provider(&self, name: &str) -> Result<ProxyProvider> {
+        self.get(&["providers", "proxies", name]).await
+    }
+
+    /// 让内核重新拉取代理集合
+    pub async fn update_proxy_provider(&self, name: &str) -> Result<()> {
+        Self::send(self.request(Method::PUT, &["providers", "proxies", name])).await?;
+        Ok(())
+    }
+
+    /// 对代理集合中的节点做健康检查, 结果写入各节点的延迟记录
+    pub async fn healthcheck_proxy_provider(&self, name: &str) -> Result<()> {
+        let req = self.request(Method::GET, &["providers", "proxies", name, "healthcheck"]);
+        Self::send(req).await?;
+        Ok(())
+    }
+
+    pub async fn rule_providers(&self) -> Result<HashMap<String, RuleProvider>> {
+        let providers: Providers<RuleProvider> = self.get(&["providers", "rules"]).await?;
+        Ok(providers.providers)
+    }
+
+    pub async fn update_rule_provider(&self, name: &str) -> Result<()> {
+        Self::send(self.request(Method::PUT, &["providers", "rules", name])).await?;
+        Ok(())
+    }
+
+    pub async fn rules(&self) -> Result<Vec<Rule>> {
+        let rules: Rules = self.get(&["rules"]).await?;
+        Ok(rules.rules)
+    }
+
+    pub async fn connections(&self) -> Result<Connections> {
+        self.get(&["connections"]).await
+    }
+
+    /// 关闭一个连接
+    pub async fn close_connection(&self, id: &str) -> Result<()> {
+        Self::send(self.request(Method::DELETE, &["connections", id])).await?;
+        Ok(())
+    }
+
+    /// 关闭所有连接
+    pub async fn close_connections(&self) -> Result<()> {
+        Self::send(self.request(Method::DELETE, &["connections"])).await?;
+        Ok(())
+    }
+
+    /// 通过 WebSocket 订阅连接信息, 内核每隔 `interval` 推送一次所有连接的快照
+    ///
+    /// 连接断开或出错后流结束, 出错时最后一项为错误
+    pub async fn watch_connections(
+        &self,
+        interval: Duration,
+    ) -> Result<BoxStream<'static, Result<Connections>>> {
+        let mut url = self.url(&["connections"]);
+        url.query_pairs_mut()
+            .append_pair("interval", &interval.as_millis().to_string());
+        let messages = self.websocket(url).await?;
+        Ok(messages
+            .map(|text| Ok(serde_json::from_str(&text?)?))
+            .boxed())
+    }
+
+    /// 建立 WebSocket 连接, 返回收到的文本消息
+    async fn websocket(&self, mut url: Url) -> Result<BoxStream<'static, Result<String>>> {
+        let scheme = match url.scheme() {
+            "https" => "wss",
+            _ => "ws",
+        };
+        let _ = url.set_scheme(scheme);
+        let mut request = url.as_str().into_client_request()?;
+        if let Some(secret) = &self.secret {
+            request
+                .headers_mut()
+                .insert(AUTHORIZATION, format!("Bearer {secret}").parse()?);
+        }
+        let connect = async {
+            Ok::<_, color_eyre::Report>(match &self.unix {
+                #[cfg(unix)]
+                Some(path) => {
+                    let stream = tokio::net::UnixStream::connect(path).await?;
+                    text_messages(tokio_tungstenite::client_async(request, stream).await?.0)
+                }
+                _ => text_messages(tokio_tungstenite::connect_async(request).await?.0),
+            })
+        };
+        tokio::time::timeout(TIMEOUT, connect)
+            .await
+            .map_err(|_| eyre!("连接内核超时"))?
+    }
+
+    /// 通过内核的 DNS 查询域名, `qtype` 为记录类型, 如 `A`, `AAAA`
+    pub async fn dns_query(&self, name: &str, qtype: &str) -> Result<DnsResponse> {
+        let req = self
+            .request(Method::GET, &["dns", "query"])
+            .query(&[("name", name), ("type", qtype)]);
+        Ok(Self::send(req).await?.json().await?)
+    }
+}
+
+/// 只保留文本消息, ping 等控制消息由 tungstenite 处理
+fn text_messages<S>(ws: S) -> BoxStream<'static, Result<String>>
+where
+    S: Stream<Item = Result<Message, tungstenite::Error>> + Send + 'static,
+{
+    ws.filter_map(|msg| async move {
+        match msg {
+            Ok(Message::Text(text)) => Some(Ok(text)),
+            Ok(_) => None,
+            Err(err) => Some(Err(err.into())),
+        }
+    })
+    .boxed()
+}
+
+fn delay_query(url: &str, timeout: Duration) -> [(&'static str, String); 2] {
+    [
+        ("url", url.to_string()),
+        ("timeout", timeout.as_millis().to_string()),
+    ]
+}
+
+/// external-controller 的地址转换为 url, 监听所有地址时通过本机地址访问
+fn base_url(addr: &str) -> String {
+    let addr = addr.trim().trim_end_matches('/');
+    if addr.starts_with("http://") || addr.starts_with("https://") {
+        return addr.to_string();
+    }
+    let addr = match addr.rsplit_once(':') {
+        Some(("" | "0.0.0.0", port)) => format!("127.0.0.1:{port}"),
+        Some(("[::]", port)) => format!("[::1]:{port}"),
+        _ => addr.to_string(),
+    };
+    format!("http://{addr}")
+}
+
+/// 请求失败是否因为连接不上内核, 通常是内核没有运行
+pub fn is_connect_error(err: &color_eyre::Report) -> bool {
+    err.downcast_ref::<reqwest::Error>()
+        .is_some_and(|err| err.is_connect() || err.is_timeout())
+}
+
+#[cfg(test)]
+mod test {
+    use wiremock::{
+        matchers::{body_json, header, method, path, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+
+    #[test]
+    fn controller_base_url() {
+        assert_eq!(base_url("127.0.0.1:9090"), "http://127.0.0.1:9090");
+        assert_eq!(base_url(":9090"), "http://127.0.0.1:9090");
+        assert_eq!(base_url("0.0.0.0:9097"), "http://127.0.0.1:9097");
+        assert_eq!(base_url("[::]:9090"), "http://[::1]:9090");
+        assert_eq!(base_url("https://clash.lan/"), "https://clash.lan");
+    }
+
+    fn ok(body: serde_json::Value) -> ResponseTemplate {
+        ResponseTemplate::new(200).set_body_json(body)
+    }
+
+    #[tokio::test]
+    async fn bearer_secret() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/version"))
+            .and(header("authorization", "Bearer s3cret"))
+            .respond_with(ok(json!({"meta": true, "version": "v1.18.10"})))
+            .with_priority(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(401).set_body_json(json!({"message": "Unauthorized"})),
+            )
+            .mount(&server)
+            .await;
+
+        let controller = Controller::new(&server.uri(), Some("s3cret".to_string()))?;
+        let version = controller.version().await?;
+        assert!(version.meta);
+        assert_eq!(version.version, "v1.18.10");
+
+        let err = Controller::new(&server.uri(), None)?
+            .version()
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "内核返回 401 Unauthorized: Unauthorized");
+        assert!(!is_connect_error(&err));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn configs_and_rules() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/configs"))
+            .respond_with(ok(json!({
+                "port": 0, "socks-port": 0, "mixed-port": 7890, "allow-lan": true,
+                "mode": "rule", "log-level": "info", "ipv6": false, "tun": {"enable": false},
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path("/configs"))
+            .and(body_json(json!({"mode": "global"})))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path("/configs"))
+            .and(query_param("force", "true"))
+            .and(body_json(json!({"path": "", "payload": "mode: rule\n"})))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/rules"))
+            .respond_with(ok(json!({"rules": [
+                {"type": "DomainSuffix", "payload": "google.com", "proxy": "Proxy", "size": -1},
+                {"type": "RuleSet", "payload": "cn", "proxy": "DIRECT", "size": 8000},
+                {"type": "Match", "payload": "", "proxy": "Proxy"},
+            ]})))
+            .mount(&server)
+            .await;
+
+        let controller = Controller::new(&server.uri(), None)?;
+        let configs = controller.configs().await?;
+        assert_eq!(configs.mixed_port, 7890);
+        assert!(configs.allow_lan);
+        assert_eq!(configs.mode, TunnelMode::Rule);
+        controller
+            .patch_configs(&ConfigsPatch {
+                mode: Some(TunnelMode::Global),
+                ..Default::default()
+            })
+            .await?;
+        controller.reload("mode: rule\n").await?;
+
+        let rules = controller.rules().await?;
+        assert_eq!(rules.len(), 3);
+        assert_eq!(rules[1].rtype, "RuleSet");
+        assert_eq!(rules[1].size, 8000);
+        assert_eq!(rules[2].proxy, "Proxy");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn proxies_and_delay() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/proxies"))
+            .respond_with(ok(json!({"proxies": {
+                "Proxy": {"name": "Proxy", "type": "Selector", "udp": true,
+                    "all": ["HK 01/a", "DIRECT"], "now": "HK 01/a", "history": []},
+                "HK 01/a": {"name": "HK 01/a", "type": "Shadowsocks", "udp": true,
+                    "history": [{"time": "2024-10-01T00:00:00Z", "delay": 0},
+                                {"time": "2024-10-01T00:01:00Z", "delay": 86}]},
+                "DIRECT": {"name": "DIRECT", "type": "Direct", "udp": true, "history": []},
+            }})))
+            .mount(&server)
+            .await;
+        // 节点名中的空格和 `/` 需要转义
+        Mock::given(method("PUT"))
+            .and(path("/proxies/Proxy"))
+            .and(body_json(json!({"name": "HK 01/a"})))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/proxies/HK%2001%2Fa/delay"))
+            .and(query_param("url", "https://www.gstatic.com/generate_204"))
+            .and(query_param("timeout", "5000"))
+            .respond_with(ok(json!({"delay": 120})))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/proxies/slow/delay"))
+            .respond_with(ResponseTemplate::new(504).set_body_json(json!({"message": "Timeout"})))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/proxies/broken/delay"))
+            .respond_with(
+                ResponseTemplate::new(503)
+                    .set_body_json(json!({"message": "An error occurred in the delay test"})),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/group/Proxy/delay"))
+            .respond_with(ok(json!({"HK 01/a": 120, "DIRECT": 3})))
+            .mount(&server)
+            .await;
+
+        let controller = Controller::new(&server.uri(), None)?;
+        let proxies = controller.proxies().await?;
+        let group = &proxies["Proxy"];
+        assert!(group.is_group());
+        assert_eq!(group.now.as_deref(), Some("HK 01/a"));
+        assert_eq!(group.last_delay(), None);
+        let node = &proxies["HK 01/a"];
+        assert!(!node.is_group());
+        assert_eq!(node.ptype, "Shadowsocks");
+        assert_eq!(node.last_delay(), Some(86));
+
+        controller.select_proxy("Proxy", "HK 01/a").await?;
+
+        let url = "https://www.gstatic.com/generate_204";
+        let timeout = Duration::from_secs(5);
+        assert_eq!(
+            controller.proxy_delay("HK 01/a", url, timeout).await?,
+            Delay::Ms(120)
+        );
+        assert_eq!(
+            controller.proxy_delay("slow", url, timeout).await?,
+            Delay::Timeout
+        );
+        assert_eq!(
+            controller.proxy_delay("broken", url, timeout).await?,
+            Delay::Failed
+        );
+        // 节点不存在时内核返回 404, 属于请求出错
+        let err = controller
+            .proxy_delay("missing", url, timeout)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().starts_with("内核返回 404"));
+
+        let delays = controller.group_delay("Proxy", url, timeout).await?;
+        assert_eq!(delays.get("DIRECT"), Some(&3));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn providers() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/providers/proxies"))
+            .respond_with(ok(json!({"providers": {
+                "airport": {"name": "airport", "type": "Proxy", "vehicleType": "HTTP",
+                    "updatedAt": "2024-10-01T00:00:00Z",
+                    "subscriptionInfo": {"Upload": 1, "Download": 2, "Total": 1024, "Expire": 0},
+                    "proxies": [{"name": "JP 01", "type": "Trojan", "udp": true, "history": []}]},
+            }})))
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path("/providers/proxies/airport"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/providers/proxies/airport/healthcheck"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/providers/rules"))
+            .respond_with(ok(json!({"providers": {
+                "cn": {"name": "cn", "type": "Rule", "vehicleType": "HTTP", "behavior": "Domain",
+                    "format": "YamlRule", "ruleCount": 8000, "updatedAt": "2024-10-01T00:00:00Z"},
+            }})))
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path("/providers/rules/cn"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let controller = Controller::new(&server.uri(), None)?;
+        let providers = controller.proxy_providers().await?;
+        let airport = &providers["airport"];
+        assert_eq!(airport.vehicle_type, "HTTP");
+        assert_eq!(airport.proxies[0].name, "JP 01");
+        assert_eq!(
+            airport.subscription_info.as_ref().map(|s| s.total),
+            Some(1024)
+        );
+        controller.update_proxy_provider("airport").await?;
+        controller.healthcheck_proxy_provider("airport").await?;
+
+        let rules = controller.rule_providers().await?;
+        assert_eq!(rules["cn"].rule_count, 8000);
+        assert_eq!(rules["cn"].behavior, "Domain");
+        controller.update_rule_provider("cn").await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn connections_and_dns() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/connections"))
+            .respond_with(ok(json!({
+                "downloadTotal": 2048, "uploadTotal": 1024, "memory": 0,
+                "connections": [{
+                    "id": "c1", "upload": 10, "download": 20, "start": "2024-10-01T00:00:00Z",
+                    "chains": ["HK 01", "Proxy"], "rule": "DomainSuffix", "rulePayload": "google.com",
+                    "metadata": {"network": "tcp", "type": "Mixed", "sourceIP": "127.0.0.1",
+                        "destinationIP": "142.250.0.1", "sourcePort": "51000",
+                        "destinationPort": "443", "host": "www.google.com",
+                        "process": "curl", "processPath": "/usr/bin/curl"},
+                }],
+            })))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        // 没有连接时 connections 为 null
+        Mock::given(method("GET"))
+            .and(path("/connections"))
+            .respond_with(ok(
+                json!({"downloadTotal": 0, "uploadTotal": 0, "connections": null}),
+            ))
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/connections/c1"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/connections"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/dns/query"))
+            .and(query_param("name", "example.com"))
+            .and(query_param("type", "A"))
+            .respond_with(ok(json!({
+                "Status": 0, "TC": false, "RD": true, "RA": true, "AD": false, "CD": false,
+                "Question": [{"Name": "example.com.", "Qtype": 1, "Qclass": 1}],
+                "Answer": [{"name": "example.com.", "type": 1, "TTL": 300, "data": "93.184.215.14"}],
+            })))
+            .mount(&server)
+            .await;
+
+        let controller = Controller::new(&server.uri(), None)?;
+        let connections = controller.connections().await?;
+        assert_eq!(connections.download_total, 2048);
+        let conn = &connections.connections[0];
+        assert_eq!(conn.metadata.host, "www.google.com");
+        assert_eq!(conn.metadata.destination_ip, "142.250.0.1");
+        assert_eq!(conn.metadata.process, "curl");
+        assert_eq!(conn.chains, ["HK 01", "Proxy"]);
+        controller.close_connection(&conn.id).await?;
+        controller.close_connections().await?;
+        assert!(controller.connections().await?.connections.is_empty());
+
+        let dns = controller.dns_query("example.com", "A").await?;
+        assert_eq!(dns.status, 0);
+        assert_eq!(dns.question[0].qtype, 1);
+        assert_eq!(dns.answer[0].ttl, 300);
+        assert_eq!(dns.answer[0].data, "93.184.215.14");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn connections_websocket() -> Result<()> {
+        use futures::SinkExt;
+        use tokio_tungstenite::tungstenite::handshake::server::{Request, Response};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await?;
+            let mut uri = String::new();
+            let mut auth = None;
+            // 回调的签名由 tungstenite 决定
+            #[allow(clippy::result_large_err)]
+            let callback = |req: &Request, res: Response| {
+                uri = req.uri().to_string();
+                auth = req.headers().get("authorization").cloned();
+                Ok(res)
+            };
+            let mut ws = tokio_tungstenite::accept_hdr_async(stream, callback).await?;
+            for total in [100, 300] {
+                let snapshot =
+                    json!({"downloadTotal": total, "uploadTotal": 0, "connections": null});
+                ws.send(Message::Text(snapshot.to_string())).await?;
+            }
+            ws.close(None).await?;
+            Ok::<_, color_eyre::Report>((uri, auth))
+        });
+
+        let controller = Controller::new(&addr.to_string(), Some("s3cret".to_string()))?;
+        let snapshots: Vec<Connections> = controller
+            .watch_connections(Duration::from_millis(500))
+            .await?
+            .map(|c| c.unwrap())
+            .collect()
+            .await;
+        let totals: Vec<u64> = snapshots.iter().map(|c| c.download_total).collect();
+        assert_eq!(totals, [100, 300]);
+        let (uri, auth) = server.await??;
+        assert_eq!(uri, "/connections?interval=500");
+        assert_eq!(auth.unwrap(), "Bearer s3cret");
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn unix_socket() -> Result<()> {
+        use tokio::{
+            io::{AsyncReadExt, AsyncWriteExt},
+            net::UnixListener,
+        };
+
+        let sock = std::env::temp_dir().join(format!("lazyclash-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&sock);
+        let listener = UnixListener::bind(&sock)?;
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await?;
+            let mut buf = vec![0; 4096];
+            let mut len = 0;
+            while !buf[..len].windows(4).any(|w| w == b"\r\n\r\n") {
+                match stream.read(&mut buf[len..]).await? {
+                    0 => break,
+                    n => len += n,
+                }
+            }
+            let request = String::from_utf8_lossy(&buf[..len]).to_string();
+            let body = r#"{"meta":true,"version":"v1.18.10"}"#;
+            let res = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            stream.write_all(res.as_bytes()).await?;
+            Ok::<_, std::io::Error>(request)
+        });
+
+        let config = serde_yaml::from_str::<Mapping>(&format!(
+            "external-controller: 127.0.0.1:1\nexternal-controller-unix: {}\n",
+            sock.display()
+        ))?;
+        let controller = Controller::from_config(&config)?.unwrap();
+        let version = controller.version().await?;
+        assert_eq!(version.version, "v1.18.10");
+        let request = server.await??;
+        assert!(request.starts_with("GET /version HTTP/1.1"));
+        std::fs::remove_file(&sock)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,263 @@
+//! external-controller 接口返回的数据结构, 只列出界面用到的字段, 其余字段忽略
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// `GET /version`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Version {
+    pub version: String,
+    /// mihomo (clash.meta) 内核
+    pub meta: bool,
+    /// clash premium 内核
+    pub premium: bool,
+}
+
+/// 代理模式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TunnelMode {
+    #[default]
+    Rule,
+    Global,
+    Direct,
+}
+
+/// `GET /configs`, 内核当前生效的配置
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Configs {
+    pub port: u16,
+    pub socks_port: u16,
+    pub redir_port: u16,
+    pub tproxy_port: u16,
+    pub mixed_port: u16,
+    pub allow_lan: bool,
+    pub bind_address: String,
+    pub mode: TunnelMode,
+    pub log_level: String,
+    pub ipv6: bool,
+}
+
+/// `PATCH /configs`, 只修改设置了的字段
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ConfigsPatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub socks_port: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mixed_port: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow_lan: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<TunnelMode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_level: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ipv6: Option<bool>,
+}
+
+/// 延迟测试记录, delay 为 0 表示测试失败
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct DelayHistory {
+    pub time: String,
+    pub delay: u16,
+}
+
+/// `GET /proxies` 中的节点或策略组
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ProxyInfo {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ptype: String,
+    pub udp: bool,
+    pub history: Vec<DelayHistory>,
+    /// 策略组的成员, 节点为空
+    pub all: Vec<String>,
+    /// 策略组当前选中的成员
+    pub now: Option<String>,
+    pub hidden: bool,
+}
+
+impl ProxyInfo {
+    pub fn is_group(&self) -> bool {
+        !self.all.is_empty()
+    }
+
+    /// 最近一次延迟测试的结果, 没有测试过时为 None, 测试失败时为 Some(0)
+    pub fn last_delay(&self) -> Option<u16> {
+        self.history.last().map(|h| h.delay)
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub(super) struct Proxies {
+    pub proxies: HashMap<String, ProxyInfo>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub(super) struct DelayResult {
+    pub delay: u16,
+}
+
+/// 一次延迟测试的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delay {
+    /// 延迟, 单位毫秒
+    Ms(u16),
+    /// 在超时时间内没有响应
+    Timeout,
+    /// 节点不可用, 如连接被拒绝或握手失败
+    Failed,
+}
+
+/// 订阅信息, 来自 subscription-userinfo 响应头
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct SubscriptionInfo {
+    pub upload: u64,
+    pub download: u64,
+    pub total: u64,
+    pub expire: u64,
+}
+
+/// `GET /providers/proxies` 中的代理集合
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ProxyProvider {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ptype: String,
+    pub vehicle_type: String,
+    pub proxies: Vec<ProxyInfo>,
+    pub updated_at: Option<String>,
+    pub subscription_info: Option<SubscriptionInfo>,
+}
+
+/// `GET /providers/rules` 中的规则集合
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RuleProvider {
+    pub name: String,
+    pub behavior: String,
+    pub format: String,
+    pub vehicle_type: String,
+    pub rule_count: usize,
+    pub updated_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub(super) struct Providers<T> {
+    pub providers: HashMap<String, T>,
+}
+
+/// `GET /rules`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Rule {
+    #[serde(rename = "type")]
+    pub rtype: String,
+    pub payload: String,
+    pub proxy: String,
+    /// 规则集合中的规则数, 其他规则为 -1
+    pub size: i64,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub(super) struct Rules {
+    pub rules: Vec<Rule>,
+}
+
+/// `GET /connections`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Connections {
+    pub download_total: u64,
+    pub upload_total: u64,
+    /// 没有连接时内核返回 null
+    #[serde(deserialize_with = "null_as_default")]
+    pub connections: Vec<Connection>,
+    pub memory: u64,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Connection {
+    pub id: String,
+    pub metadata: Metadata,
+    /// 累计上传的字节数
+    pub upload: u64,
+    /// 累计下载的字节数
+    pub download: u64,
+    pub start: String,
+    /// 经过的代理, 从最后使用的节点到最外层的策略组
+    pub chains: Vec<String>,
+    pub rule: String,
+    pub rule_payload: String,
+}
+
+/// 连接的元数据
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Metadata {
+    pub network: String,
+    #[serde(rename = "type")]
+    pub ctype: String,
+    #[serde(rename = "sourceIP")]
+    pub source_ip: String,
+    #[serde(rename = "destinationIP")]
+    pub destination_ip: String,
+    pub source_port: String,
+    pub destination_port: String,
+    pub host: String,
+    pub dns_mode: String,
+    pub process: String,
+    pub process_path: String,
+}
+
+/// `GET /dns/query` 的结果, 字段名和 DNS 报文一致
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct DnsResponse {
+    /// 响应码, 0 为 NOERROR, 3 为 NXDOMAIN
+    #[serde(rename = "Status")]
+    pub status: u16,
+    #[serde(rename = "Question", deserialize_with = "null_as_default")]
+    pub question: Vec<DnsQuestion>,
+    #[serde(rename = "Answer")]
+    pub answer: Vec<DnsAnswer>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct DnsQuestion {
+    pub name: String,
+    pub qtype: u16,
+    pub qclass: u16,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct DnsAnswer {
+    pub name: String,
+    /// 记录类型的编号, 1 为 A, 28 为 AAAA
+    #[serde(rename = "type")]
+    pub rtype: u16,
+    #[serde(rename = "TTL")]
+    pub ttl: u32,
+    pub data: String,
+}
+
+fn null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Ok(Option::deserialize(deserializer)?.unwrap_or_default())
+}
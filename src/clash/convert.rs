@@ -0,0 +1,959 @@
+use std::collections::HashMap;
+
+use base64::{
+    engine::general_purpose::{STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD},
+    Engine,
+};
+use color_eyre::{eyre::eyre, Result};
+use serde::Deserialize;
+use serde_yaml::{Mapping, Value};
+use tracing::warn;
+use url::{form_urlencoded, Url};
+
+use super::{ClashConfig, Proxy, ProxyGroup};
+
+/// 支持转换的分享链接协议
+const SCHEMES: [&str; 7] = [
+    "ss://",
+    "ssr://",
+    "vmess://",
+    "vless://",
+    "trojan://",
+    "hysteria2://",
+    "hy2://",
+];
+
+/// 默认代理组名称
+const DEFAULT_GROUP: &str = "PROXY";
+
+/// 订阅内容如果是分享链接列表 (明文或 base64 编码), 返回其中的链接
+pub fn uri_list(data: &str) -> Option<Vec<String>> {
+    let text = if has_uri(data) {
+        data.to_string()
+    } else {
+        let compact: String = data.split_whitespace().collect();
+        String::from_utf8(decode_base64(&compact)?).ok()?
+    };
+    if !has_uri(&text) {
+        return None;
+    }
+    Some(
+        text.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect(),
+    )
+}
+
+fn has_uri(text: &str) -> bool {
+    text.lines()
+        .map(str::trim)
+        .any(|line| SCHEMES.iter().any(|s| line.starts_with(s)))
+}
+
+/// 将分享链接列表转换为 clash 配置, 所有节点放入默认的 select 代理组
+///
+/// 无法解析的链接会被跳过
+pub fn uri_list_to_config(uris: &[String]) -> Result<ClashConfig> {
+    let mut names: HashMap<String, usize> = HashMap::new();
+    let mut proxies = Vec::new();
+    for uri in uris {
+        match parse_uri(uri) {
+            Ok(mut proxy) => {
+                // clash 不允许重名的节点
+                let count = names.entry(proxy.name.clone()).or_insert(0);
+                *count += 1;
+                if *count > 1 {
+                    proxy.name = format!("{} {}", proxy.name, count);
+                }
+                proxies.push(proxy);
+            }
+            Err(err) => warn!("跳过无法解析的链接: {err}"),
+        }
+    }
+    if proxies.is_empty() {
+        return Err(eyre!("订阅中没有可以解析的节点链接"));
+    }
+    let group = ProxyGroup {
+        name: DEFAULT_GROUP.to_string(),
+        gtype: "select".to_string(),
+        proxies: proxies.iter().map(|p| p.name.clone()).collect(),
+        ..Default::default()
+    };
+    Ok(ClashConfig {
+        proxies,
+        proxy_groups: vec![group],
+        rules: vec![format!("MATCH,{DEFAULT_GROUP}")],
+        ..Default::default()
+    })
+}
+
+/// 解析单个分享链接
+pub fn parse_uri(uri: &str) -> Result<Proxy> {
+    let uri = uri.trim();
+    let (scheme, _) = uri
+        .split_once("://")
+        .ok_or_else(|| eyre!("不是分享链接: {uri}"))?;
+    match scheme {
+        "ss" => parse_ss(uri),
+        "ssr" => parse_ssr(uri),
+        "vmess" => parse_vmess(uri),
+        "vless" => parse_vless(uri),
+        "trojan" => parse_trojan(uri),
+        "hysteria2" | "hy2" => parse_hysteria2(uri),
+        _ => Err(eyre!("不支持的协议: {scheme}")),
+    }
+}
+
+fn decode_base64(data: &str) -> Option<Vec<u8>> {
+    let data = data.trim();
+    [STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD]
+        .iter()
+        .find_map(|engine| engine.decode(data).ok())
+}
+
+fn decode_base64_str(data: &str) -> Result<String> {
+    let bytes = decode_base64(data).ok_or_else(|| eyre!("base64 解码失败: {data}"))?;
+    Ok(String::from_utf8(bytes)?)
+}
+
+fn percent_decode(data: &str) -> String {
+    percent_encoding::percent_decode_str(data)
+        .decode_utf8_lossy()
+        .to_string()
+}
+
+fn set(extra: &mut Mapping, key: &str, value: impl Into<Value>) {
+    extra.insert(key.into(), value.into());
+}
+
+/// 链接中 `#` 后的节点名称, 没有时使用 server:port
+fn fragment_name(url: &Url) -> String {
+    match url.fragment() {
+        Some(name) if !name.is_empty() => percent_decode(name),
+        _ => format!(
+            "{}:{}",
+            url.host_str().unwrap_or_default(),
+            url.port().unwrap_or_default()
+        ),
+    }
+}
+
+fn server_port(url: &Url) -> Result<(String, u16)> {
+    let port = url.port().ok_or_else(|| eyre!("缺少端口: {url}"))?;
+    Ok((server(url)?, port))
+}
+
+/// 服务器地址, ipv6 地址去掉方括号
+fn server(url: &Url) -> Result<String> {
+    let host = url
+        .host_str()
+        .ok_or_else(|| eyre!("缺少服务器地址: {url}"))?
+        .trim_matches(['[', ']'])
+        .to_string();
+    Ok(host)
+}
+
+fn query(url: &Url) -> HashMap<String, String> {
+    url.query_pairs()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+fn is_true(value: Option<&String>) -> bool {
+    matches!(value.map(String::as_str), Some("1" | "true"))
+}
+
+/// ss://base64(method:password)@server:port#name 或 ss://base64(method:password@server:port)#name
+fn parse_ss(uri: &str) -> Result<Proxy> {
+    let body = &uri["ss://".len()..];
+    let (body, name) = match body.split_once('#') {
+        Some((body, name)) => (body, Some(percent_decode(name))),
+        None => (body, None),
+    };
+    let (body, plugin) = match body.split_once('?') {
+        Some((body, query)) => (body, Some(query)),
+        None => (body, None),
+    };
+    let (userinfo, host) = match body.rsplit_once('@') {
+        Some((userinfo, host)) => {
+            let userinfo = decode_base64_str(userinfo).unwrap_or_else(|_| percent_decode(userinfo));
+            (userinfo, host.to_string())
+        }
+        None => {
+            let decoded = decode_base64_str(body)?;
+            let (userinfo, host) = decoded
+                .rsplit_once('@')
+                .ok_or_else(|| eyre!("ss 链接格式错误: {uri}"))?;
+            (userinfo.to_string(), host.to_string())
+        }
+    };
+    let (cipher, password) = userinfo
+        .split_once(':')
+        .ok_or_else(|| eyre!("ss 链接缺少加密方式或密码: {uri}"))?;
+    let (server, port) = host
+        .trim_end_matches('/')
+        .rsplit_once(':')
+        .ok_or_else(|| eyre!("ss 链接缺少端口: {uri}"))?;
+    let port: u16 = port.parse()?;
+    let server = server.trim_matches(['[', ']']).to_string();
+
+    let mut extra = Mapping::new();
+    set(&mut extra, "cipher", cipher);
+    set(&mut extra, "password", password);
+    if let Some(plugin) = plugin {
+        let url = Url::parse(&format!("http://localhost/?{plugin}"))?;
+        if let Some(plugin) = query(&url).get("plugin") {
+            set_ss_plugin(&mut extra, plugin);
+        }
+    }
+    Ok(Proxy {
+        name: name.unwrap_or(format!("{server}:{port}")),
+        ptype: "ss".to_string(),
+        server: Some(server),
+        port: Some(port),
+        udp: Some(true),
+        extra,
+    })
+}
+
+/// plugin=obfs-local;obfs=http;obfs-host=example.com
+fn set_ss_plugin(extra: &mut Mapping, plugin: &str) {
+    let mut parts = plugin.split(';');
+    let name = parts.next().unwrap_or_default();
+    let opts: HashMap<&str, &str> = parts.filter_map(|p| p.split_once('=')).collect();
+    let mut plugin_opts = Mapping::new();
+    match name {
+        "obfs-local" | "simple-obfs" => {
+            set(extra, "plugin", "obfs");
+            if let Some(mode) = opts.get("obfs") {
+                set(&mut plugin_opts, "mode", *mode);
+            }
+            if let Some(host) = opts.get("obfs-host") {
+                set(&mut plugin_opts, "host", *host);
+            }
+        }
+        "v2ray-plugin" => {
+            set(extra, "plugin", "v2ray-plugin");
+            set(&mut plugin_opts, "mode", "websocket");
+            if plugin.contains(";tls") {
+                set(&mut plugin_opts, "tls", true);
+            }
+            if let Some(host) = opts.get("host") {
+                set(&mut plugin_opts, "host", *host);
+            }
+            if let Some(path) = opts.get("path") {
+                set(&mut plugin_opts, "path", *path);
+            }
+        }
+        _ => {
+            set(extra, "plugin", name);
+        }
+    }
+    if !plugin_opts.is_empty() {
+        set(extra, "plugin-opts", plugin_opts);
+    }
+}
+
+/// ssr://base64(server:port:protocol:method:obfs:base64(password)/?obfsparam=&protoparam=&remarks=)
+fn parse_ssr(uri: &str) -> Result<Proxy> {
+    let decoded = decode_base64_str(&uri["ssr://".len()..])?;
+    let (main, params) = match decoded.split_once("/?") {
+        Some((main, params)) => (main, params),
+        None => (decoded.as_str(), ""),
+    };
+    let parts: Vec<&str> = main.rsplitn(6, ':').collect();
+    let [password, obfs, cipher, protocol, port, server] = parts[..] else {
+        return Err(eyre!("ssr 链接格式错误: {uri}"));
+    };
+    let params: HashMap<&str, String> = params
+        .split('&')
+        .filter_map(|p| p.split_once('='))
+        .map(|(k, v)| (k, decode_base64_str(v).unwrap_or_default()))
+        .collect();
+    let port: u16 = port.parse()?;
+
+    let mut extra = Mapping::new();
+    set(&mut extra, "cipher", cipher);
+    set(&mut extra, "password", decode_base64_str(password)?);
+    set(&mut extra, "protocol", protocol);
+    set(&mut extra, "obfs", obfs);
+    if let Some(param) = params.get("protoparam").filter(|p| !p.is_empty()) {
+        set(&mut extra, "protocol-param", param.as_str());
+    }
+    if let Some(param) = params.get("obfsparam").filter(|p| !p.is_empty()) {
+        set(&mut extra, "obfs-param", param.as_str());
+    }
+    let name = params
+        .get("remarks")
+        .filter(|r| !r.is_empty())
+        .cloned()
+        .unwrap_or(format!("{server}:{port}"));
+    Ok(Proxy {
+        name,
+        ptype: "ssr".to_string(),
+        server: Some(server.to_string()),
+        port: Some(port),
+        udp: Some(true),
+        extra,
+    })
+}
+
+/// vmess 链接中的 json, 部分客户端会把数字写成字符串
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct VmessLink {
+    ps: Option<String>,
+    add: String,
+    port: Value,
+    id: String,
+    aid: Value,
+    scy: Option<String>,
+    net: Option<String>,
+    #[serde(rename = "type")]
+    header_type: Option<String>,
+    host: Option<String>,
+    path: Option<String>,
+    tls: Option<String>,
+    sni: Option<String>,
+    alpn: Option<String>,
+    fp: Option<String>,
+}
+
+fn value_to_u64(value: &Value) -> Option<u64> {
+    match value {
+        Value::Number(n) => n.as_u64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// vmess://base64(json)
+fn parse_vmess(uri: &str) -> Result<Proxy> {
+    let json = decode_base64_str(&uri["vmess://".len()..])?;
+    let link: VmessLink = serde_json::from_str(&json)?;
+    let port = value_to_u64(&link.port)
+        .and_then(|p| u16::try_from(p).ok())
+        .ok_or_else(|| eyre!("vmess 链接端口错误: {uri}"))?;
+
+    let mut extra = Mapping::new();
+    set(&mut extra, "uuid", link.id.as_str());
+    set(&mut extra, "alterId", value_to_u64(&link.aid).unwrap_or(0));
+    set(
+        &mut extra,
+        "cipher",
+        link.scy.filter(|s| !s.is_empty()).unwrap_or("auto".into()),
+    );
+    if link.tls.as_deref() == Some("tls") {
+        set(&mut extra, "tls", true);
+        if let Some(sni) = link.sni.filter(|s| !s.is_empty()) {
+            set(&mut extra, "servername", sni);
+        }
+        if let Some(alpn) = link.alpn.filter(|s| !s.is_empty()) {
+            let alpn: Vec<Value> = alpn.split(',').map(|a| a.trim().into()).collect();
+            set(&mut extra, "alpn", alpn);
+        }
+        if let Some(fp) = link.fp.filter(|s| !s.is_empty()) {
+            set(&mut extra, "client-fingerprint", fp);
+        }
+    }
+    let network = link.net.unwrap_or("tcp".to_string());
+    set_transport(
+        &mut extra,
+        &network,
+        link.host.as_deref(),
+        link.path.as_deref(),
+        link.header_type.as_deref(),
+    );
+    Ok(Proxy {
+        name: link
+            .ps
+            .filter(|ps| !ps.is_empty())
+            .unwrap_or(format!("{}:{port}", link.add)),
+        ptype: "vmess".to_string(),
+        server: Some(link.add),
+        port: Some(port),
+        udp: Some(true),
+        extra,
+    })
+}
+
+/// ws/grpc/h2/http 传输层参数
+fn set_transport(
+    extra: &mut Mapping,
+    network: &str,
+    host: Option<&str>,
+    path: Option<&str>,
+    header_type: Option<&str>,
+) {
+    let host = host.filter(|h| !h.is_empty());
+    let path = path.filter(|p| !p.is_empty());
+    match network {
+        "ws" | "httpupgrade" => {
+            set(extra, "network", "ws");
+            let mut opts = Mapping::new();
+            if let Some(path) = path {
+                set(&mut opts, "path", path);
+            }
+            if let Some(host) = host {
+                let mut headers = Mapping::new();
+                set(&mut headers, "Host", host);
+                set(&mut opts, "headers", headers);
+            }
+            if network == "httpupgrade" {
+                set(&mut opts, "v2ray-http-upgrade", true);
+            }
+            set(extra, "ws-opts", opts);
+        }
+        "grpc" => {
+            set(extra, "network", "grpc");
+            let mut opts = Mapping::new();
+            if let Some(path) = path {
+                set(&mut opts, "grpc-service-name", path);
+            }
+            set(extra, "grpc-opts", opts);
+        }
+        "h2" => {
+            set(extra, "network", "h2");
+            let mut opts = Mapping::new();
+            if let Some(path) = path {
+                set(&mut opts, "path", path);
+            }
+            if let Some(host) = host {
+                let hosts: Vec<Value> = host.split(',').map(|h| h.trim().into()).collect();
+                set(&mut opts, "host", hosts);
+            }
+            set(extra, "h2-opts", opts);
+        }
+        "tcp" if header_type == Some("http") => {
+            set(extra, "network", "http");
+            let mut opts = Mapping::new();
+            if let Some(path) = path {
+                set(&mut opts, "path", vec![Value::from(path)]);
+            }
+            if let Some(host) = host {
+                let mut headers = Mapping::new();
+                set(&mut headers, "Host", vec![Value::from(host)]);
+                set(&mut opts, "headers", headers);
+            }
+            set(extra, "http-opts", opts);
+        }
+        _ => {}
+    }
+}
+
+/// vless://uuid@server:port?security=tls&type=ws&path=/&host=&sni=&flow=#name
+fn parse_vless(uri: &str) -> Result<Proxy> {
+    let url = Url::parse(uri)?;
+    let (server, port) = server_port(&url)?;
+    let params = query(&url);
+
+    let mut extra = Mapping::new();
+    set(&mut extra, "uuid", percent_decode(url.username()));
+    if let Some(flow) = params.get("flow").filter(|f| !f.is_empty()) {
+        set(&mut extra, "flow", flow.as_str());
+    }
+    match params.get("security").map(String::as_str) {
+        Some("tls") => {
+            set(&mut extra, "tls", true);
+        }
+        Some("reality") => {
+            set(&mut extra, "tls", true);
+            let mut opts = Mapping::new();
+            if let Some(pbk) = params.get("pbk") {
+                set(&mut opts, "public-key", pbk.as_str());
+            }
+            if let Some(sid) = params.get("sid") {
+                set(&mut opts, "short-id", sid.as_str());
+            }
+            set(&mut extra, "reality-opts", opts);
+        }
+        _ => {}
+    }
+    if let Some(sni) = params.get("sni").filter(|s| !s.is_empty()) {
+        set(&mut extra, "servername", sni.as_str());
+    }
+    if let Some(fp) = params.get("fp").filter(|s| !s.is_empty()) {
+        set(&mut extra, "client-fingerprint", fp.as_str());
+    }
+    if is_true(params.get("allowInsecure")) {
+        set(&mut extra, "skip-cert-verify", true);
+    }
+    let network = params.get("type").map(String::as_str).unwrap_or("tcp");
+    let path = match network {
+        "grpc" => params.get("serviceName"),
+        _ => params.get("path"),
+    };
+    set_transport(
+        &mut extra,
+        network,
+        params.get("host").map(String::as_str),
+        path.map(String::as_str),
+        params.get("headerType").map(String::as_str),
+    );
+    Ok(Proxy {
+        name: fragment_name(&url),
+        ptype: "vless".to_string(),
+        server: Some(server),
+        port: Some(port),
+        udp: Some(true),
+        extra,
+    })
+}
+
+/// trojan://password@server:port?sni=&type=ws&path=&allowInsecure=1#name
+fn parse_trojan(uri: &str) -> Result<Proxy> {
+    let url = Url::parse(uri)?;
+    let (server, port) = server_port(&url)?;
+    let params = query(&url);
+
+    let mut extra = Mapping::new();
+    set(&mut extra, "password", percent_decode(url.username()));
+    if let Some(sni) = params
+        .get("sni")
+        .or(params.get("peer"))
+        .filter(|s| !s.is_empty())
+    {
+        set(&mut extra, "sni", sni.as_str());
+    }
+    if is_true(params.get("allowInsecure")) {
+        set(&mut extra, "skip-cert-verify", true);
+    }
+    if let Some(fp) = params.get("fp").filter(|s| !s.is_empty()) {
+        set(&mut extra, "client-fingerprint", fp.as_str());
+    }
+    let network = params.get("type").map(String::as_str).unwrap_or("tcp");
+    let path = match network {
+        "grpc" => params.get("serviceName"),
+        _ => params.get("path"),
+    };
+    set_transport(
+        &mut extra,
+        network,
+        params.get("host").map(String::as_str),
+        path.map(String::as_str),
+        None,
+    );
+    Ok(Proxy {
+        name: fragment_name(&url),
+        ptype: "trojan".to_string(),
+        server: Some(server),
+        port: Some(port),
+        udp: Some(true),
+        extra,
+    })
+}
+
+/// hysteria2://password@server:port?sni=&obfs=salamander&obfs-password=&insecure=1#name
+fn parse_hysteria2(uri: &str) -> Result<Proxy> {
+    let url = Url::parse(uri)?;
+    let server = server(&url)?;
+    // 端口默认为 443
+    let port = url.port().unwrap_or(443);
+    let params = query(&url);
+
+    let mut extra = Mapping::new();
+    let password = match url.password() {
+        Some(password) => format!("{}:{}", url.username(), password),
+        None => url.username().to_string(),
+    };
+    set(&mut extra, "password", percent_decode(&password));
+    if let Some(sni) = params.get("sni").filter(|s| !s.is_empty()) {
+        set(&mut extra, "sni", sni.as_str());
+    }
+    if is_true(params.get("insecure")) {
+        set(&mut extra, "skip-cert-verify", true);
+    }
+    if let Some(obfs) = params.get("obfs").filter(|s| !s.is_empty() && *s != "none") {
+        set(&mut extra, "obfs", obfs.as_str());
+        if let Some(password) = params.get("obfs-password") {
+            set(&mut extra, "obfs-password", password.as_str());
+        }
+    }
+    if let Some(ports) = params.get("mport").filter(|s| !s.is_empty()) {
+        set(&mut extra, "ports", ports.as_str());
+    }
+    Ok(Proxy {
+        name: fragment_name(&url),
+        ptype: "hysteria2".to_string(),
+        server: Some(server),
+        port: Some(port),
+        udp: Some(true),
+        extra,
+    })
+}
+
+/// 生成节点的分享链接, 是 parse_uri 的逆操作
+///
+/// 只支持能从分享链接解析的协议, ssr 节点的参数较多且已很少使用, 不支持生成
+pub fn to_uri(proxy: &Proxy) -> Result<String> {
+    let server = proxy
+        .server
+        .as_deref()
+        .ok_or_else(|| eyre!("节点 {} 缺少服务器地址", proxy.name))?;
+    let port = proxy
+        .port
+        .ok_or_else(|| eyre!("节点 {} 缺少端口", proxy.name))?;
+    // ipv6 地址需要加上方括号
+    let host = match server.contains(':') {
+        true => format!("[{server}]:{port}"),
+        false => format!("{server}:{port}"),
+    };
+    let extra = &proxy.extra;
+    let name = percent_encode(&proxy.name);
+    let uri = match proxy.ptype.as_str() {
+        "ss" => {
+            let userinfo = format!(
+                "{}:{}",
+                get_str(extra, "cipher").unwrap_or_default(),
+                get_str(extra, "password").unwrap_or_default()
+            );
+            let mut uri = format!("ss://{}@{host}", URL_SAFE_NO_PAD.encode(userinfo));
+            if let Some(plugin) = ss_plugin(extra) {
+                let query = form_urlencoded::Serializer::new(String::new())
+                    .append_pair("plugin", &plugin)
+                    .finish();
+                uri = format!("{uri}/?{query}");
+            }
+            format!("{uri}#{name}")
+        }
+        "vmess" => {
+            let (network, host, path, header_type) = transport(extra);
+            let tls = extra.get("tls").and_then(Value::as_bool) == Some(true);
+            let alpn = extra.get("alpn").and_then(Value::as_sequence).map(|alpn| {
+                alpn.iter()
+                    .filter_map(Value::as_str)
+                    .collect::<Vec<_>>()
+                    .join(",")
+            });
+            let json = serde_json::json!({
+                "v": "2",
+                "ps": proxy.name,
+                "add": server,
+                "port": port.to_string(),
+                "id": get_str(extra, "uuid").unwrap_or_default(),
+                "aid": extra.get("alterId").and_then(value_to_u64).unwrap_or(0).to_string(),
+                "scy": get_str(extra, "cipher").unwrap_or("auto"),
+                "net": network,
+                "type": header_type.unwrap_or("none"),
+                "host": host.unwrap_or_default(),
+                "path": path.unwrap_or_default(),
+                "tls": if tls { "tls" } else { "" },
+                "sni": get_str(extra, "servername").unwrap_or_default(),
+                "alpn": alpn.unwrap_or_default(),
+                "fp": get_str(extra, "client-fingerprint").unwrap_or_default(),
+            });
+            format!("vmess://{}", STANDARD.encode(json.to_string()))
+        }
+        "vless" => {
+            let mut params = form_urlencoded::Serializer::new(String::new());
+            if let Some(flow) = get_str(extra, "flow") {
+                params.append_pair("flow", flow);
+            }
+            if let Some(reality) = extra.get("reality-opts") {
+                params.append_pair("security", "reality");
+                if let Some(pbk) = reality.get("public-key").and_then(Value::as_str) {
+                    params.append_pair("pbk", pbk);
+                }
+                if let Some(sid) = reality.get("short-id").and_then(Value::as_str) {
+                    params.append_pair("sid", sid);
+                }
+            } else if extra.get("tls").and_then(Value::as_bool) == Some(true) {
+                params.append_pair("security", "tls");
+            }
+            if let Some(sni) = get_str(extra, "servername") {
+                params.append_pair("sni", sni);
+            }
+            append_common(&mut params, extra);
+            let uuid = percent_encode(get_str(extra, "uuid").unwrap_or_default());
+            format!("vless://{uuid}@{host}?{}#{name}", params.finish())
+        }
+        "trojan" => {
+            let mut params = form_urlencoded::Serializer::new(String::new());
+            if let Some(sni) = get_str(extra, "sni") {
+                params.append_pair("sni", sni);
+            }
+            append_common(&mut params, extra);
+            let password = percent_encode(get_str(extra, "password").unwrap_or_default());
+            format!("trojan://{password}@{host}?{}#{name}", params.finish())
+        }
+        "hysteria2" => {
+            let mut params = form_urlencoded::Serializer::new(String::new());
+            if let Some(sni) = get_str(extra, "sni") {
+                params.append_pair("sni", sni);
+            }
+            if extra.get("skip-cert-verify").and_then(Value::as_bool) == Some(true) {
+                params.append_pair("insecure", "1");
+            }
+            if let Some(obfs) = get_str(extra, "obfs") {
+                params.append_pair("obfs", obfs);
+                if let Some(password) = get_str(extra, "obfs-password") {
+                    params.append_pair("obfs-password", password);
+                }
+            }
+            if let Some(ports) = get_str(extra, "ports") {
+                params.append_pair("mport", ports);
+            }
+            let password = percent_encode(get_str(extra, "password").unwrap_or_default());
+            format!("hysteria2://{password}@{host}?{}#{name}", params.finish())
+        }
+        other => return Err(eyre!("不支持生成 {other} 节点的分享链接")),
+    };
+    Ok(uri)
+}
+
+fn percent_encode(data: &str) -> String {
+    percent_encoding::utf8_percent_encode(data, percent_encoding::NON_ALPHANUMERIC).to_string()
+}
+
+fn get_str<'a>(extra: &'a Mapping, key: &str) -> Option<&'a str> {
+    extra
+        .get(key)
+        .and_then(Value::as_str)
+        .filter(|s| !s.is_empty())
+}
+
+/// ss 节点的 plugin 参数, 如 obfs-local;obfs=http;obfs-host=example.com
+fn ss_plugin(extra: &Mapping) -> Option<String> {
+    let plugin = get_str(extra, "plugin")?;
+    let opts = extra.get("plugin-opts");
+    let opt = |key: &str| opts.and_then(|o| o.get(key)).and_then(Value::as_str);
+    let mut parts = Vec::new();
+    match plugin {
+        "obfs" => {
+            parts.push("obfs-local".to_string());
+            if let Some(mode) = opt("mode") {
+                parts.push(format!("obfs={mode}"));
+            }
+            if let Some(host) = opt("host") {
+                parts.push(format!("obfs-host={host}"));
+            }
+        }
+        "v2ray-plugin" => {
+            parts.push("v2ray-plugin".to_string());
+            if opts.and_then(|o| o.get("tls")).and_then(Value::as_bool) == Some(true) {
+                parts.push("tls".to_string());
+            }
+            if let Some(host) = opt("host") {
+                parts.push(format!("host={host}"));
+            }
+            if let Some(path) = opt("path") {
+                parts.push(format!("path={path}"));
+            }
+        }
+        other => parts.push(other.to_string()),
+    }
+    Some(parts.join(";"))
+}
+
+/// 节点的传输层参数 (network, host, path, headerType), 是 set_transport 的逆操作
+fn transport(
+    extra: &Mapping,
+) -> (
+    &'static str,
+    Option<String>,
+    Option<String>,
+    Option<&'static str>,
+) {
+    let opts = |key: &str| extra.get(key).cloned().unwrap_or_default();
+    let text = |value: Option<&Value>| value.and_then(Value::as_str).map(str::to_string);
+    match get_str(extra, "network") {
+        Some("ws") => {
+            let opts = opts("ws-opts");
+            let network = match opts.get("v2ray-http-upgrade").and_then(Value::as_bool) {
+                Some(true) => "httpupgrade",
+                _ => "ws",
+            };
+            let host = text(opts.get("headers").and_then(|h| h.get("Host")));
+            (network, host, text(opts.get("path")), None)
+        }
+        Some("grpc") => {
+            let opts = opts("grpc-opts");
+            ("grpc", None, text(opts.get("grpc-service-name")), None)
+        }
+        Some("h2") => {
+            let opts = opts("h2-opts");
+            let host = opts.get("host").and_then(Value::as_sequence).map(|hosts| {
+                hosts
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .collect::<Vec<_>>()
+                    .join(",")
+            });
+            ("h2", host, text(opts.get("path")), None)
+        }
+        Some("http") => {
+            let opts = opts("http-opts");
+            let first = |value: Option<&Value>| {
+                text(value.and_then(Value::as_sequence).and_then(|s| s.first()))
+            };
+            let host = first(opts.get("headers").and_then(|h| h.get("Host")));
+            ("tcp", host, first(opts.get("path")), Some("http"))
+        }
+        _ => ("tcp", None, None, None),
+    }
+}
+
+/// vless 和 trojan 共用的 tls 和传输层参数
+fn append_common(params: &mut form_urlencoded::Serializer<String>, extra: &Mapping) {
+    if let Some(fp) = get_str(extra, "client-fingerprint") {
+        params.append_pair("fp", fp);
+    }
+    if extra.get("skip-cert-verify").and_then(Value::as_bool) == Some(true) {
+        params.append_pair("allowInsecure", "1");
+    }
+    let (network, host, path, header_type) = transport(extra);
+    params.append_pair("type", network);
+    if let Some(host) = host {
+        params.append_pair("host", &host);
+    }
+    if let Some(path) = path {
+        let key = match network {
+            "grpc" => "serviceName",
+            _ => "path",
+        };
+        params.append_pair(key, &path);
+    }
+    if let Some(header_type) = header_type {
+        params.append_pair("headerType", header_type);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn extra<'a>(proxy: &'a Proxy, key: &str) -> Option<&'a Value> {
+        proxy.extra.get(key)
+    }
+
+    #[test]
+    fn ss_sip002() -> Result<()> {
+        let uri = format!(
+            "ss://{}@1.2.3.4:8388/?plugin=obfs-local%3Bobfs%3Dhttp%3Bobfs-host%3Dexample.com#HK%2001",
+            STANDARD.encode("aes-256-gcm:pass")
+        );
+        let proxy = parse_uri(&uri)?;
+        assert_eq!(proxy.name, "HK 01");
+        assert_eq!(proxy.server.as_deref(), Some("1.2.3.4"));
+        assert_eq!(proxy.port, Some(8388));
+        assert_eq!(extra(&proxy, "cipher"), Some(&"aes-256-gcm".into()));
+        assert_eq!(extra(&proxy, "password"), Some(&"pass".into()));
+        assert_eq!(extra(&proxy, "plugin"), Some(&"obfs".into()));
+        Ok(())
+    }
+
+    #[test]
+    fn ss_legacy() -> Result<()> {
+        let uri = format!(
+            "ss://{}#JP",
+            STANDARD.encode("chacha20-ietf-poly1305:p@ss@jp.example.com:443")
+        );
+        let proxy = parse_uri(&uri)?;
+        assert_eq!(proxy.server.as_deref(), Some("jp.example.com"));
+        assert_eq!(proxy.port, Some(443));
+        assert_eq!(extra(&proxy, "password"), Some(&"p@ss".into()));
+        Ok(())
+    }
+
+    #[test]
+    fn ssr() -> Result<()> {
+        let body = format!(
+            "ssr.example.com:8080:auth_aes128_md5:aes-256-cfb:tls1.2_ticket_auth:{}/?remarks={}",
+            URL_SAFE_NO_PAD.encode("secret"),
+            URL_SAFE_NO_PAD.encode("SG 01")
+        );
+        let proxy = parse_uri(&format!("ssr://{}", URL_SAFE_NO_PAD.encode(body)))?;
+        assert_eq!(proxy.name, "SG 01");
+        assert_eq!(proxy.port, Some(8080));
+        assert_eq!(extra(&proxy, "password"), Some(&"secret".into()));
+        assert_eq!(extra(&proxy, "protocol"), Some(&"auth_aes128_md5".into()));
+        Ok(())
+    }
+
+    #[test]
+    fn vmess() -> Result<()> {
+        let json = r#"{"v":"2","ps":"US 01","add":"us.example.com","port":"443","id":"b831381d-6324-4d53-ad4f-8cda48b30811","aid":"0","net":"ws","type":"none","host":"cdn.example.com","path":"/ws","tls":"tls"}"#;
+        let proxy = parse_uri(&format!("vmess://{}", STANDARD.encode(json)))?;
+        assert_eq!(proxy.name, "US 01");
+        assert_eq!(proxy.port, Some(443));
+        assert_eq!(extra(&proxy, "network"), Some(&"ws".into()));
+        assert_eq!(extra(&proxy, "tls"), Some(&true.into()));
+        assert_eq!(extra(&proxy, "cipher"), Some(&"auto".into()));
+        Ok(())
+    }
+
+    #[test]
+    fn vless_reality() -> Result<()> {
+        let uri = "vless://b831381d-6324-4d53-ad4f-8cda48b30811@v.example.com:443?security=reality&sni=www.example.com&fp=chrome&pbk=KEY&sid=ab&type=grpc&serviceName=svc&flow=xtls-rprx-vision#TW";
+        let proxy = parse_uri(uri)?;
+        assert_eq!(proxy.name, "TW");
+        assert_eq!(extra(&proxy, "flow"), Some(&"xtls-rprx-vision".into()));
+        assert_eq!(extra(&proxy, "network"), Some(&"grpc".into()));
+        assert!(extra(&proxy, "reality-opts").is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn trojan_and_hysteria2() -> Result<()> {
+        let proxy =
+            parse_uri("trojan://pass@t.example.com:443?sni=t.example.com&allowInsecure=1#KR")?;
+        assert_eq!(proxy.ptype, "trojan");
+        assert_eq!(extra(&proxy, "skip-cert-verify"), Some(&true.into()));
+        let proxy = parse_uri("hy2://pass@h.example.com?obfs=salamander&obfs-password=x#DE")?;
+        assert_eq!(proxy.ptype, "hysteria2");
+        assert_eq!(proxy.port, Some(443));
+        assert_eq!(extra(&proxy, "obfs"), Some(&"salamander".into()));
+        let proxy = parse_uri("hysteria2://pass@[2001:db8::1]#v6")?;
+        assert_eq!(proxy.server.as_deref(), Some("2001:db8::1"));
+        assert_eq!(proxy.port, Some(443));
+        assert!(to_uri(&proxy)?.contains("@[2001:db8::1]:443"));
+        Ok(())
+    }
+
+    #[test]
+    fn base64_list() -> Result<()> {
+        let list =
+            "trojan://a@t.example.com:443#A\ntrojan://b@t.example.com:443#A\nhttp://ignored\n";
+        let uris = uri_list(&STANDARD.encode(list)).unwrap();
+        let config = uri_list_to_config(&uris)?;
+        assert_eq!(config.proxies.len(), 2);
+        assert_eq!(config.proxies[1].name, "A 2");
+        assert_eq!(config.proxy_groups[0].proxies, vec!["A", "A 2"]);
+        assert!(config.warnings().is_empty());
+        assert!(uri_list("proxies: []").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn share_uri_roundtrip() -> Result<()> {
+        let uris = [
+            "ss://YWVzLTI1Ni1nY206cGFzcw@1.2.3.4:8388/?plugin=obfs-local%3Bobfs%3Dhttp#HK 01".to_string(),
+            "vless://b831381d-6324-4d53-ad4f-8cda48b30811@v.example.com:443?security=reality&sni=www.example.com&pbk=KEY&sid=ab&type=grpc&serviceName=svc#TW".to_string(),
+            "trojan://p%40ss@t.example.com:443?sni=t.example.com&allowInsecure=1&type=ws&path=/ws&host=cdn.example.com#KR".to_string(),
+            "hy2://pass@[2001:db8::1]:8443?obfs=salamander&obfs-password=x#DE".to_string(),
+            format!(
+                "vmess://{}",
+                STANDARD.encode(r#"{"ps":"US","add":"us.example.com","port":443,"id":"id","aid":0,"net":"h2","host":"a.com","path":"/h2","tls":"tls"}"#)
+            ),
+        ];
+        for uri in uris {
+            let proxy = parse_uri(&uri)?;
+            let again = parse_uri(&to_uri(&proxy)?)?;
+            assert_eq!(again.name, proxy.name);
+            assert_eq!(again.ptype, proxy.ptype);
+            assert_eq!(again.server, proxy.server);
+            assert_eq!(again.port, proxy.port);
+            assert_eq!(again.extra, proxy.extra, "{uri}");
+        }
+        let ssr = Proxy {
+            ptype: "ssr".to_string(),
+            server: Some("s.example.com".to_string()),
+            port: Some(80),
+            ..Default::default()
+        };
+        assert!(to_uri(&ssr).is_err());
+        Ok(())
+    }
+}
@@ -1,6 +1,11 @@
-use clap::Parser;
+use std::path::PathBuf;
 
-use crate::config::{get_config_dir, get_data_dir, get_subscribe_dir};
+use clap::{Parser, Subcommand};
+
+use crate::{
+    bundle::ImportMode,
+    config::{get_config_dir, get_data_dir, get_subscribe_dir},
+};
 
 #[derive(Parser, Debug)]
 #[command(author, version = version(), about)]
@@ -12,6 +17,27 @@ pub struct Cli {
     /// Frame rate, i.e. number of frames per second
     #[arg(short, long, value_name = "FLOAT", default_value_t = 60.0)]
     pub frame_rate: f64,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Export all profiles, subscribe files and the config into one archive
+    Export {
+        /// Archive path, e.g. lazyclash.tar.gz
+        path: PathBuf,
+    },
+    /// Import profiles and the config from an archive created by export
+    Import {
+        /// Archive path
+        path: PathBuf,
+
+        /// How to handle the existing profiles
+        #[arg(short, long, value_enum, default_value_t = ImportMode::Merge)]
+        mode: ImportMode,
+    },
 }
 
 const VERSION_MESSAGE: &str = concat!(
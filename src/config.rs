@@ -1,6 +1,6 @@
 #![allow(dead_code)] // Remove this once you start using the code
 
-use std::{ env, path::PathBuf};
+use std::{ env, path::PathBuf, time::Duration};
 
 use color_eyre::Result;
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
@@ -10,6 +10,8 @@ use ratatui::style::{Color, Modifier, Style};
 use serde::Deserialize;
 use tracing::error;
 
+use crate::prfitem::PrfOption;
+
 const CONFIG: &str = include_str!("../.config/config.json5");
 
 #[derive(Clone, Debug, Deserialize, Default)]
@@ -20,6 +22,76 @@ pub struct AppConfig {
     pub config_dir: PathBuf,
     #[serde(default)]
     pub subscribe_dir: PathBuf,
+    /// 拉取订阅的全局 http 配置, 可被订阅自身的配置覆盖
+    #[serde(default)]
+    pub fetch: PrfOption,
+    /// clash/mihomo 内核的配置
+    #[serde(default)]
+    pub core: CoreConfig,
+    /// 节点延迟测试的配置
+    #[serde(default)]
+    pub delay: DelayConfig,
+}
+
+/// 内核相关的全局配置, 设置的字段会覆盖运行时配置中的同名字段
+#[derive(Clone, Debug, Deserialize, Default)]
+pub struct CoreConfig {
+    /// 内核读取的配置文件, 默认为数据目录下的 config.yaml
+    #[serde(default)]
+    pub config_path: Option<PathBuf>,
+    #[serde(default)]
+    pub mixed_port: Option<u16>,
+    #[serde(default)]
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub socks_port: Option<u16>,
+    #[serde(default)]
+    pub allow_lan: Option<bool>,
+    #[serde(default)]
+    pub external_controller: Option<String>,
+    /// 内核监听的 Unix socket, 设置后 lazyclash 通过它连接内核
+    #[serde(default)]
+    pub external_controller_unix: Option<String>,
+    #[serde(default)]
+    pub secret: Option<String>,
+}
+
+impl CoreConfig {
+    pub fn config_path(&self) -> PathBuf {
+        self.config_path
+            .clone()
+            .unwrap_or_else(|| get_data_dir().join("config.yaml"))
+    }
+}
+
+/// 节点延迟测试的配置, 不设置时使用默认值
+#[derive(Clone, Debug, Deserialize, Default)]
+pub struct DelayConfig {
+    /// 同时测试的节点数
+    #[serde(default)]
+    pub concurrency: Option<usize>,
+    /// 测试时访问的地址
+    #[serde(default)]
+    pub url: Option<String>,
+    /// 超时时间, 单位毫秒
+    #[serde(default)]
+    pub timeout: Option<u64>,
+}
+
+impl DelayConfig {
+    pub fn concurrency(&self) -> usize {
+        self.concurrency.unwrap_or(8).max(1)
+    }
+
+    pub fn url(&self) -> String {
+        self.url
+            .clone()
+            .unwrap_or_else(|| "https://www.gstatic.com/generate_204".to_string())
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout.unwrap_or(5000))
+    }
 }
 
 #[derive(Clone, Debug, Default, Deserialize)]
@@ -34,10 +106,14 @@ pub struct Config {
 
 lazy_static! {
     pub static ref PROJECT_NAME: String = env!("CARGO_CRATE_NAME").to_uppercase().to_string();
-    pub static ref DATA_FOLDER: Option<PathBuf> =
+    // 测试时使用临时目录, 不读写用户的订阅文件
+    pub static ref DATA_FOLDER: Option<PathBuf> = if cfg!(test) {
+        Some(env::temp_dir().join(format!("lazyclash-test-{}", std::process::id())))
+    } else {
         env::var(format!("{}_DATA", PROJECT_NAME.clone()))
             .ok()
-            .map(PathBuf::from);
+            .map(PathBuf::from)
+    };
     pub static ref CONFIG_FOLDER: Option<PathBuf> =
         env::var(format!("{}_CONFIG", PROJECT_NAME.clone()))
             .ok()
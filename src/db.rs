@@ -1,13 +1,21 @@
-use std::path::{Path, PathBuf};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
-use crate::config::get_data_dir;
-use crate::prfitem::PrfItem;
+use crate::config::{get_data_dir, get_subscribe_dir};
+use crate::prfitem::{PrfItem, PrfOption, PrfRevision};
 use chrono::Utc;
-use color_eyre::Result;
-use sqlx::{migrate::MigrateDatabase, sqlite::SqlitePoolOptions, FromRow, Pool, Row, Sqlite};
+use color_eyre::{eyre::eyre, Result};
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous},
+    FromRow, Pool, Row, Sqlite,
+};
 use tracing::debug;
 use tracing_subscriber::fmt::format;
 
+pub mod migration;
+
 pub fn get_db_file() -> Result<PathBuf> {
     let mut db_file = get_data_dir();
     if !db_file.exists() {
@@ -18,52 +26,363 @@ pub fn get_db_file() -> Result<PathBuf> {
     Ok(db_file)
 }
 
-pub async fn init() -> Result<()> {
-    init_db(get_db_file()?).await?;
-    Ok(())
+/// prf_items 表的列
+const PRF_ITEMS_COLUMNS: &str =
+    "uid, itype, name, file, desc, url, selected, extra, updated, home, update_interval, option";
+
+/// 打开数据库并执行迁移, 返回共享连接池的订阅仓库
+pub async fn init() -> Result<PrfRepo> {
+    let pool = connect(get_db_file()?).await?;
+    restore_prf_files(&pool).await?;
+    // 运行迁移
+    let version = migration::migrate(&pool).await?;
+    debug!("数据库版本: {version}");
+    Ok(PrfRepo::new(pool))
+}
+
+/// 连接数据库, 不存在时创建
+///
+/// 使用 WAL 模式, 后台更新订阅时界面的查询不会被阻塞
+async fn connect(db_file: PathBuf) -> Result<Pool<Sqlite>> {
+    let options = SqliteConnectOptions::new()
+        .filename(db_file)
+        .create_if_missing(true)
+        .journal_mode(SqliteJournalMode::Wal)
+        .synchronous(SqliteSynchronous::Normal)
+        .busy_timeout(Duration::from_secs(5));
+    let pool = SqlitePoolOptions::new()
+        .max_connections(4)
+        .connect_with(options)
+        .await?;
+    Ok(pool)
 }
 
-async fn init_db(db_file: PathBuf) -> Result<()> {
-    let db_url = format!("sqlite://{}", db_file.display());
-    if !Sqlite::database_exists(&db_url).await.unwrap_or(false) {
-        Sqlite::create_database(&db_url).await?;
+/// 旧版本的订阅内容同时保存在 prf_items.file_data 列中,
+/// 迁移删除该列前把缺失的文件写回 subscribe 目录
+async fn restore_prf_files(pool: &Pool<Sqlite>) -> Result<()> {
+    let columns: i64 = sqlx::query(
+        "SELECT COUNT(*) AS n FROM pragma_table_info('prf_items') WHERE name = 'file_data'",
+    )
+    .fetch_one(pool)
+    .await?
+    .try_get("n")?;
+    if columns == 0 {
+        return Ok(());
     }
-    let poll = SqlitePoolOptions::new()
-        .connect(&format!("sqlite:{}", db_file.display()))
+    let rows = sqlx::query("SELECT file, file_data FROM prf_items WHERE file_data IS NOT NULL")
+        .fetch_all(pool)
         .await?;
-    // 运行迁移
-    let migration = r#"
-        CREATE TABLE IF NOT EXISTS prf_items (
-            uid TEXT,
-            itype TEXT,
-            name TEXT,
-            file TEXT,
-            desc TEXT,
-            url TEXT,
-            selected INTEGER, -- 在 SQLite 中，布尔类型被存储为 INTEGER (0 或 1)
-            extra TEXT, -- 使用 TEXT 类型存储 JSON 数据
-            updated INTEGER,
-            home TEXT,
-            file_data TEXT
-        );
-    "#;
-    sqlx::query(migration).execute(&poll).await?;
+    for row in rows {
+        let item = PrfItem {
+            file: row.try_get("file")?,
+            file_data: row.try_get("file_data")?,
+            ..Default::default()
+        };
+        if prf_file_path(&item).is_some_and(|path| !path.exists()) {
+            write_prf_file(&item).await?;
+        }
+    }
     Ok(())
 }
 
-pub async fn insert_prf_item(item: &PrfItem) -> Result<i64> {
-    let pool = SqlitePoolOptions::new()
-        .connect(&format!("sqlite:///{}", get_db_file()?.display()))
+/// 每个订阅最多保留的历史版本数
+const MAX_REVISIONS: i64 = 30;
+
+fn option_json(item: &PrfItem) -> Option<String> {
+    item.option
+        .as_ref()
+        .filter(|option| !option.is_empty())
+        .map(|option| serde_json::to_string(option).unwrap_or_default())
+}
+
+/// 订阅的数据库操作, 内部共享同一个连接池
+#[derive(Clone)]
+pub struct PrfRepo {
+    pool: Pool<Sqlite>,
+}
+
+impl PrfRepo {
+    pub fn new(pool: Pool<Sqlite>) -> Self {
+        Self { pool }
+    }
+
+    /// 保存新建的订阅及其配置文件, 返回保存后的订阅
+    ///
+    /// 文件名和其他订阅或已有的文件重复时加上序号, 不会覆盖其他订阅的文件
+    pub async fn create(&self, mut item: PrfItem) -> Result<PrfItem> {
+        let file = file_name(item.file.as_deref().unwrap_or("config"));
+        let uid = item.uid.clone().unwrap_or_default();
+        item.file = Some(unique_file(&self.list().await?, &uid, &file));
+        write_prf_file(&item).await?;
+        self.upsert(&item).await?;
+        Ok(item)
+    }
+
+    /// 按 uid 插入或更新订阅, 返回受影响的行数
+    pub async fn upsert(&self, item: &PrfItem) -> Result<u64> {
+        upsert_item(&self.pool, item).await
+    }
+
+    /// 在一个事务中保存多个订阅, `replace` 为 true 时先删除所有已有的订阅和历史版本
+    ///
+    /// 任何一个订阅保存失败时都不会修改数据库
+    pub async fn save_all(&self, items: &[PrfItem], replace: bool) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        if replace {
+            sqlx::query("DELETE FROM prf_revisions")
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query("DELETE FROM prf_items")
+                .execute(&mut *tx)
+                .await?;
+        }
+        for item in items {
+            upsert_item(&mut *tx, item).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// 查询所有订阅
+    pub async fn list(&self) -> Result<Vec<PrfItem>> {
+        let items =
+            sqlx::query_as::<_, PrfItem>(&format!("SELECT {PRF_ITEMS_COLUMNS} FROM prf_items"))
+                .fetch_all(&self.pool)
+                .await?;
+
+        Ok(items)
+    }
+
+    /// 按 uid 查询订阅
+    pub async fn get(&self, uid: &str) -> Result<Option<PrfItem>> {
+        let item = sqlx::query_as::<_, PrfItem>(&format!(
+            "SELECT {PRF_ITEMS_COLUMNS} FROM prf_items WHERE uid = ?"
+        ))
+        .bind(uid)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(item)
+    }
+
+    /// 查询订阅链接相同的订阅
+    pub async fn get_by_url(&self, url: &str) -> Result<Option<PrfItem>> {
+        let item = sqlx::query_as::<_, PrfItem>(&format!(
+            "SELECT {PRF_ITEMS_COLUMNS} FROM prf_items WHERE url = ?"
+        ))
+        .bind(url.trim())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(item)
+    }
+
+    /// 按 uid 更新订阅, 返回受影响的行数
+    pub async fn update(&self, item: &PrfItem) -> Result<u64> {
+        let now = Utc::now();
+        let extra_json = item
+            .extra
+            .as_ref()
+            .map(|extra| serde_json::to_string(extra).unwrap_or_default());
+        let option_json = option_json(item);
+
+        let rows = sqlx::query("UPDATE prf_items SET itype = ?, name = ?, file = ?, desc = ?, url = ?, selected = ?, extra = ?, updated = ?, home = ?, update_interval = ?, option = ? WHERE uid = ?")
+            .bind(item.itype.as_deref())
+            .bind(item.name.as_deref())
+            .bind(item.file.as_deref())
+            .bind(item.desc.as_deref())
+            .bind(item.url.as_deref())
+            .bind(item.selected.map(|b| b as i32))
+            .bind(extra_json.as_deref())
+            .bind(item.updated.map_or(now.timestamp(), |v| v as i64))
+            .bind(item.home.as_deref())
+            .bind(item.update_interval.map(|v| v as i64))
+            .bind(option_json.as_deref())
+            .bind(item.uid.as_deref())
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(rows)
+    }
+
+    /// 将 uid 对应的配置设为唯一选中的配置
+    pub async fn select(&self, uid: &str) -> Result<()> {
+        if self.get(uid).await?.is_none() {
+            return Err(eyre!("订阅不存在: {uid}"));
+        }
+        sqlx::query("UPDATE prf_items SET selected = (uid = ?)")
+            .bind(uid)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// 设置订阅的自动更新间隔, 单位分钟
+    pub async fn set_interval(&self, uid: &str, interval: u64) -> Result<u64> {
+        let rows = sqlx::query("UPDATE prf_items SET update_interval = ? WHERE uid = ?")
+            .bind(interval as i64)
+            .bind(uid)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        Ok(rows)
+    }
+
+    /// 设置订阅的 http 配置, 空配置表示使用全局配置
+    pub async fn set_option(&self, uid: &str, option: &PrfOption) -> Result<u64> {
+        let option_json = if option.is_empty() {
+            None
+        } else {
+            Some(serde_json::to_string(option)?)
+        };
+        let rows = sqlx::query("UPDATE prf_items SET option = ? WHERE uid = ?")
+            .bind(option_json)
+            .bind(uid)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        Ok(rows)
+    }
+
+    /// 删除订阅及其配置文件, 返回受影响的行数
+    pub async fn delete(&self, uid: &str) -> Result<u64> {
+        let item = self.get(uid).await?;
+        let rows = sqlx::query("DELETE FROM prf_items WHERE uid = ?")
+            .bind(uid)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        sqlx::query("DELETE FROM prf_revisions WHERE uid = ?")
+            .bind(uid)
+            .execute(&self.pool)
+            .await?;
+        if let Some(path) = item.as_ref().and_then(prf_file_path) {
+            if path.exists() {
+                debug!("删除订阅文件: {}", path.display());
+                tokio::fs::remove_file(path).await?;
+            }
+        }
+        Ok(rows)
+    }
+
+    /// 重命名订阅, 配置文件同时改为新名称
+    pub async fn rename(&self, uid: &str, name: &str) -> Result<()> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(eyre!("名称不能为空"));
+        }
+        let mut item = self
+            .get(uid)
+            .await?
+            .ok_or_else(|| eyre!("订阅不存在: {uid}"))?;
+        let old_path = prf_file_path(&item);
+        let file = file_name(name);
+        item.name = Some(name.to_string());
+        if item.file.as_deref() != Some(file.as_str()) {
+            item.file = Some(file);
+            let new_path = prf_file_path(&item).ok_or_else(|| eyre!("订阅文件名为空"))?;
+            if new_path.exists() {
+                return Err(eyre!("文件已存在: {}", new_path.display()));
+            }
+            if let Some(old_path) = old_path.filter(|p| p.exists()) {
+                debug!(
+                    "重命名订阅文件: {} -> {}",
+                    old_path.display(),
+                    new_path.display()
+                );
+                tokio::fs::rename(old_path, new_path).await?;
+            }
+        }
+        sqlx::query("UPDATE prf_items SET name = ?, file = ? WHERE uid = ?")
+            .bind(item.name.as_deref())
+            .bind(item.file.as_deref())
+            .bind(uid)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// 将订阅当前的内容保存为历史版本, 超出数量的旧版本会被删除
+    pub async fn insert_revision(&self, item: &PrfItem) -> Result<()> {
+        let (Some(uid), Some(file_data)) = (item.uid.as_deref(), item.file_data.as_deref()) else {
+            return Ok(());
+        };
+        sqlx::query("INSERT INTO prf_revisions (uid, updated, file_data) VALUES (?, ?, ?)")
+            .bind(uid)
+            .bind(item.updated.map_or(Utc::now().timestamp(), |v| v as i64))
+            .bind(file_data)
+            .execute(&self.pool)
+            .await?;
+        sqlx::query(
+            "DELETE FROM prf_revisions WHERE uid = ? AND id NOT IN (SELECT id FROM prf_revisions WHERE uid = ? ORDER BY updated DESC, id DESC LIMIT ?)",
+        )
+        .bind(uid)
+        .bind(uid)
+        .bind(MAX_REVISIONS)
+        .execute(&self.pool)
         .await?;
+        Ok(())
+    }
+
+    /// 查询订阅的历史版本, 按时间从新到旧排列
+    pub async fn revisions(&self, uid: &str) -> Result<Vec<PrfRevision>> {
+        let revisions = sqlx::query_as::<_, PrfRevision>(
+            "SELECT id, updated, file_data FROM prf_revisions WHERE uid = ? ORDER BY updated DESC, id DESC",
+        )
+        .bind(uid)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(revisions)
+    }
+
+    /// 回滚到指定的历史版本, 当前内容会先保存为历史版本, 返回回滚后的订阅
+    pub async fn rollback(&self, uid: &str, id: i64) -> Result<PrfItem> {
+        let mut item = self
+            .get(uid)
+            .await?
+            .ok_or_else(|| eyre!("订阅不存在: {uid}"))?;
+        let revision = sqlx::query_as::<_, PrfRevision>(
+            "SELECT id, updated, file_data FROM prf_revisions WHERE id = ? AND uid = ?",
+        )
+        .bind(id)
+        .bind(uid)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| eyre!("历史版本不存在: {id}"))?;
+        item.file_data = read_prf_file(&item).await.ok();
+        self.insert_revision(&item).await?;
+        item.file_data = Some(revision.file_data);
+        // 使用当前时间, 避免回滚后马上被自动更新覆盖
+        item.updated = Some(Utc::now().timestamp() as usize);
+        write_prf_file(&item).await?;
+        self.update(&item).await?;
+        Ok(item)
+    }
+}
+
+/// 按 uid 插入或更新订阅, uid 已存在时更新其他列
+async fn upsert_item<'e, E>(executor: E, item: &PrfItem) -> Result<u64>
+where
+    E: sqlx::Executor<'e, Database = Sqlite>,
+{
     let now = Utc::now();
     // 将 PrfExtra 序列化为 JSON 字符串
     let extra_json = item
         .extra
         .as_ref()
         .map(|extra| serde_json::to_string(extra).unwrap_or_default());
+    let option_json = option_json(item);
 
-    // 构建插入语句
-    let mut query = sqlx::query("INSERT INTO prf_items (uid, itype, name, file, desc, url, selected, extra, updated, home, file_data) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)");
+    // 构建插入语句, uid 已存在时更新其他列
+    let sql = format!(
+            "INSERT INTO prf_items ({PRF_ITEMS_COLUMNS}) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?) \
+            ON CONFLICT(uid) DO UPDATE SET itype = excluded.itype, name = excluded.name, file = excluded.file, \
+            desc = excluded.desc, url = excluded.url, selected = excluded.selected, extra = excluded.extra, \
+            updated = excluded.updated, home = excluded.home, \
+            update_interval = excluded.update_interval, option = excluded.option"
+        );
+    let mut query = sqlx::query(&sql);
 
     query = query
         .bind(item.uid.as_deref())
@@ -74,28 +393,74 @@ pub async fn insert_prf_item(item: &PrfItem) -> Result<i64> {
         .bind(item.url.as_deref())
         .bind(item.selected.map(|b| b as i32)) // 在 SQLite 中，布尔值被存储为 1 或 0
         .bind(extra_json.as_deref())
-        .bind(now.timestamp())
+        .bind(item.updated.map_or(now.timestamp(), |v| v as i64))
         .bind(item.home.as_deref())
-        .bind(item.file_data.as_deref());
+        .bind(item.update_interval.map(|v| v as i64))
+        .bind(option_json.as_deref());
 
-    // 执行插入操作并返回插入的行的 ID
-    let row_id = query.execute(&pool).await?.last_insert_rowid();
+    let rows = query.execute(executor).await?.rows_affected();
 
-    Ok(row_id)
+    Ok(rows)
 }
 
-pub async fn query_prf_item() -> Result<Vec<PrfItem>> {
-    let pool = SqlitePoolOptions::new()
-        .connect(&format!("sqlite:///{}", get_db_file()?.display()))
-        .await?;
+/// 订阅配置文件的路径
+pub fn prf_file_path(item: &PrfItem) -> Option<PathBuf> {
+    item.file
+        .as_ref()
+        .map(|file| get_subscribe_dir().join(format!("{file}.yaml")))
+}
 
-    let items = sqlx::query_as::<_, PrfItem>(
-        "SELECT uid, itype, name, file, desc, url, selected, extra, updated, home, file_data FROM prf_items",
-    )
-   .fetch_all(&pool)
-   .await?;
+/// 订阅名称对应的文件名, 文件名中不能包含路径分隔符
+pub fn file_name(name: &str) -> String {
+    name.trim().replace(['/', '\\'], "_")
+}
+
+/// 文件名被其他订阅使用, 或 subscribe 目录中已有同名文件时在后面加上序号
+pub fn unique_file(existing: &[PrfItem], uid: &str, file: &str) -> String {
+    numbered_file(file, |name| {
+        let mut owners = existing.iter().filter(|e| e.file.as_deref() == Some(name));
+        match owners.next() {
+            Some(owner) => owner.uid.as_deref() != Some(uid) || owners.next().is_some(),
+            None => get_subscribe_dir().join(format!("{name}.yaml")).exists(),
+        }
+    })
+}
 
-    Ok(items)
+/// `taken` 返回 true 时在文件名后面加上序号, 直到找到没有被使用的文件名
+pub fn numbered_file(file: &str, taken: impl Fn(&str) -> bool) -> String {
+    let mut candidate = file.to_string();
+    let mut i = 2;
+    while taken(&candidate) {
+        candidate = format!("{file} {i}");
+        i += 1;
+    }
+    candidate
+}
+
+/// 读取订阅配置文件的内容
+pub async fn read_prf_file(item: &PrfItem) -> Result<String> {
+    let Some(path) = prf_file_path(item) else {
+        return Err(eyre!("订阅文件名为空"));
+    };
+    tokio::fs::read_to_string(&path)
+        .await
+        .map_err(|err| eyre!("读取订阅文件 {} 失败: {err}", path.display()))
+}
+
+/// 将订阅内容写入 subscribe 目录, 已存在的文件会被覆盖
+pub async fn write_prf_file(item: &PrfItem) -> Result<()> {
+    let Some(path) = prf_file_path(item) else {
+        return Err(eyre!("订阅文件名为空"));
+    };
+    let Some(file_data) = item.file_data.as_deref() else {
+        return Err(eyre!("订阅文件数据为空"));
+    };
+    debug!("订阅文件路径:{}", path.display());
+    if !path.exists() {
+        tokio::fs::create_dir_all(path.parent().unwrap()).await?;
+    }
+    tokio::fs::write(path, file_data).await?;
+    Ok(())
 }
 
 impl FromRow<'_, sqlx::sqlite::SqliteRow> for PrfItem {
@@ -108,10 +473,224 @@ impl FromRow<'_, sqlx::sqlite::SqliteRow> for PrfItem {
             desc: row.try_get("desc")?,
             url: row.try_get("url")?,
             selected: row.try_get("selected")?,
-            extra: None,
-            updated: None,
+            extra: row
+                .try_get::<Option<String>, _>("extra")?
+                .and_then(|extra| serde_json::from_str(&extra).ok()),
+            updated: row
+                .try_get::<Option<i64>, _>("updated")?
+                .map(|v| v as usize),
             home: row.try_get("home")?,
+            // 订阅内容只保存在文件中, 需要时通过 read_prf_file 读取
+            file_data: None,
+            update_interval: row
+                .try_get::<Option<i64>, _>("update_interval")?
+                .map(|v| v as u64),
+            option: row
+                .try_get::<Option<String>, _>("option")?
+                .and_then(|option| serde_json::from_str(&option).ok()),
+        })
+    }
+}
+
+impl FromRow<'_, sqlx::sqlite::SqliteRow> for PrfRevision {
+    fn from_row(row: &'_ sqlx::sqlite::SqliteRow) -> Result<Self, sqlx::Error> {
+        Ok(PrfRevision {
+            id: row.try_get("id")?,
+            updated: row.try_get::<i64, _>("updated")? as usize,
             file_data: row.try_get("file_data")?,
         })
     }
 }
+
+/// 测试使用的内存数据库
+#[cfg(test)]
+pub(crate) async fn memory_repo() -> Result<PrfRepo> {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await?;
+    migration::migrate(&pool).await?;
+    Ok(PrfRepo::new(pool))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn upsert_and_query() -> Result<()> {
+        let repo = memory_repo().await?;
+        let mut item = PrfItem {
+            uid: Some("uid".to_string()),
+            name: Some("old".to_string()),
+            url: Some("https://example.com/sub".to_string()),
+            ..Default::default()
+        };
+        repo.upsert(&item).await?;
+        item.name = Some("new".to_string());
+        repo.upsert(&item).await?;
+        let items = repo.list().await?;
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].name.as_deref(), Some("new"));
+        assert!(repo
+            .get_by_url(" https://example.com/sub ")
+            .await?
+            .is_some());
+        let other = PrfItem {
+            uid: Some("other".to_string()),
+            selected: Some(true),
+            ..Default::default()
+        };
+        repo.upsert(&other).await?;
+        repo.select("uid").await?;
+        assert_eq!(repo.get("uid").await?.and_then(|i| i.selected), Some(true));
+        assert_eq!(
+            repo.get("other").await?.and_then(|i| i.selected),
+            Some(false)
+        );
+        assert!(repo.select("missing").await.is_err());
+        repo.delete("other").await?;
+        assert_eq!(repo.delete("uid").await?, 1);
+        assert!(repo.get("uid").await?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn unique_file_name() -> Result<()> {
+        let profile = |uid: &str, file: &str| PrfItem {
+            uid: Some(uid.to_string()),
+            file: Some(file.to_string()),
+            ..Default::default()
+        };
+        let existing = vec![profile("a", "work"), profile("b", "work 2")];
+        assert_eq!(unique_file(&existing, "a", "work"), "work");
+        assert_eq!(unique_file(&existing, "c", "work"), "work 3");
+        assert_eq!(unique_file(&existing, "c", "home"), "home");
+        assert_eq!(file_name(" ../a\\b "), ".._a_b");
+        // 没有订阅使用但已存在的文件也不能覆盖
+        std::fs::create_dir_all(get_subscribe_dir())?;
+        std::fs::write(get_subscribe_dir().join("orphan.yaml"), "")?;
+        assert_eq!(unique_file(&existing, "c", "orphan"), "orphan 2");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn create_local_with_same_name() -> Result<()> {
+        let repo = memory_repo().await?;
+        let dir = get_data_dir().join("local-import");
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("same.yaml");
+        std::fs::write(&path, "proxies: []\n")?;
+        let path = path.to_string_lossy().to_string();
+
+        let first = repo
+            .create(PrfItem::from_local(&path).await.map_err(|e| eyre!(e))?)
+            .await?;
+        let second = repo
+            .create(PrfItem::from_local(&path).await.map_err(|e| eyre!(e))?)
+            .await?;
+        assert_eq!(first.file.as_deref(), Some("same"));
+        assert_eq!(second.file.as_deref(), Some("same 2"));
+        // 删除其中一个不会影响另一个的文件
+        repo.delete(second.uid.as_deref().unwrap()).await?;
+        assert_eq!(read_prf_file(&first).await?, "proxies: []\n");
+        repo.delete(first.uid.as_deref().unwrap()).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rename_and_delete_files() -> Result<()> {
+        let repo = memory_repo().await?;
+        let item = |uid: &str, file: &str| PrfItem {
+            uid: Some(uid.to_string()),
+            name: Some(file.to_string()),
+            file: Some(file.to_string()),
+            file_data: Some(format!("# {uid}\n")),
+            ..Default::default()
+        };
+        let a = repo.create(item("rename-a", "rename-a")).await?;
+        let b = repo.create(item("rename-b", "rename-b")).await?;
+
+        // 文件名中的路径分隔符替换为 _, 配置文件同时改名
+        repo.rename("rename-a", "rename/x\\y").await?;
+        let renamed = repo.get("rename-a").await?.unwrap();
+        assert_eq!(renamed.name.as_deref(), Some("rename/x\\y"));
+        assert_eq!(renamed.file.as_deref(), Some("rename_x_y"));
+        assert!(!prf_file_path(&a).unwrap().exists());
+        assert_eq!(read_prf_file(&renamed).await?, "# rename-a\n");
+
+        // 文件已存在时拒绝重命名, 不修改原有的订阅和文件
+        assert!(repo.rename("rename-a", "rename-b").await.is_err());
+        assert_eq!(
+            repo.get("rename-a").await?.and_then(|i| i.file).as_deref(),
+            Some("rename_x_y")
+        );
+        assert_eq!(read_prf_file(&b).await?, "# rename-b\n");
+        assert!(repo.rename("rename-a", " ").await.is_err());
+
+        // 删除订阅时同时删除配置文件和历史版本
+        repo.insert_revision(&item("rename-b", "rename-b")).await?;
+        assert_eq!(repo.delete("rename-b").await?, 1);
+        assert!(!prf_file_path(&b).unwrap().exists());
+        assert!(repo.revisions("rename-b").await?.is_empty());
+        repo.delete("rename-a").await?;
+        assert!(!prf_file_path(&renamed).unwrap().exists());
+        assert_eq!(repo.delete("missing").await?, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn refresh_in_place() -> Result<()> {
+        use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+        let profile = |upload: u64| {
+            ResponseTemplate::new(200)
+                .insert_header(
+                    "subscription-userinfo",
+                    format!("upload={upload}; download=0; total=100; expire=0"),
+                )
+                .set_body_string("proxies: []\nrules:\n  - MATCH,DIRECT\n")
+        };
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(profile(1))
+            .up_to_n_times(1)
+            .with_priority(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .respond_with(profile(2))
+            .mount(&server)
+            .await;
+
+        let repo = memory_repo().await?;
+        let url = format!("{}/sub", server.uri());
+        let mut item = PrfItem::from_url(&url, &PrfOption::default())
+            .await
+            .map_err(|e| eyre!(e))?;
+        item.name = Some("refresh".to_string());
+        item.file = Some("refresh".to_string());
+        item.updated = Some(1);
+        let item = repo.create(item).await?;
+        let uid = item.uid.clone().unwrap();
+        repo.select(&uid).await?;
+
+        let old = repo.get(&uid).await?.unwrap();
+        let refreshed = old
+            .refresh(&PrfOption::default())
+            .await
+            .map_err(|e| eyre!(e))?;
+        assert_eq!(repo.update(&refreshed).await?, 1);
+        let new = repo.get(&uid).await?.unwrap();
+        assert_eq!(new.uid, old.uid);
+        assert_eq!(new.name.as_deref(), Some("refresh"));
+        assert_eq!(new.file.as_deref(), Some("refresh"));
+        assert_eq!(new.selected, Some(true));
+        assert!(new.updated > Some(1));
+        assert_eq!(old.extra.map(|e| e.upload), Some(1));
+        assert_eq!(new.extra.map(|e| e.upload), Some(2));
+        assert_eq!(repo.list().await?.len(), 1);
+        repo.delete(&uid).await?;
+        Ok(())
+    }
+}
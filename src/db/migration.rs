@@ -0,0 +1,208 @@
+use chrono::Utc;
+use color_eyre::{eyre::eyre, Result};
+use sqlx::{Pool, Row, Sqlite};
+use tracing::info;
+
+/// 一次数据库结构变更
+struct Migration {
+    version: i64,
+    description: &'static str,
+    sql: &'static str,
+}
+
+/// 按版本号顺序执行的迁移, 已执行的版本记录在 schema_version 表中
+///
+/// 已发布的迁移不能再修改, 修改表结构时在末尾追加新的迁移
+const MIGRATIONS: [Migration; 6] = [
+    Migration {
+        version: 1,
+        description: "create prf_items",
+        sql: r#"
+            CREATE TABLE IF NOT EXISTS prf_items (
+                uid TEXT,
+                itype TEXT,
+                name TEXT,
+                file TEXT,
+                desc TEXT,
+                url TEXT,
+                selected INTEGER, -- 在 SQLite 中，布尔类型被存储为 INTEGER (0 或 1)
+                extra TEXT, -- 使用 TEXT 类型存储 JSON 数据
+                updated INTEGER,
+                home TEXT,
+                file_data TEXT
+            );
+        "#,
+    },
+    Migration {
+        version: 2,
+        description: "add prf_items.update_interval",
+        sql: "ALTER TABLE prf_items ADD COLUMN update_interval INTEGER; -- 自动更新间隔, 单位分钟",
+    },
+    Migration {
+        version: 3,
+        description: "add prf_items.option",
+        sql: "ALTER TABLE prf_items ADD COLUMN option TEXT; -- 拉取订阅的 http 配置, JSON",
+    },
+    Migration {
+        version: 4,
+        description: "create prf_revisions",
+        sql: r#"
+            CREATE TABLE prf_revisions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                uid TEXT NOT NULL,
+                updated INTEGER NOT NULL,
+                file_data TEXT NOT NULL
+            );
+            CREATE INDEX prf_revisions_uid ON prf_revisions (uid, updated);
+        "#,
+    },
+    Migration {
+        version: 5,
+        description: "primary key on prf_items.uid",
+        // SQLite 不能直接添加主键, 需要重建表
+        sql: r#"
+            CREATE TABLE prf_items_new (
+                uid TEXT PRIMARY KEY NOT NULL,
+                itype TEXT,
+                name TEXT,
+                file TEXT,
+                desc TEXT,
+                url TEXT,
+                selected INTEGER,
+                extra TEXT,
+                updated INTEGER,
+                home TEXT,
+                file_data TEXT,
+                update_interval INTEGER,
+                option TEXT
+            );
+            INSERT OR IGNORE INTO prf_items_new
+                SELECT uid, itype, name, file, desc, url, selected, extra, updated, home,
+                    file_data, update_interval, option
+                FROM prf_items WHERE uid IS NOT NULL;
+            DROP TABLE prf_items;
+            ALTER TABLE prf_items_new RENAME TO prf_items;
+        "#,
+    },
+    Migration {
+        version: 6,
+        description: "drop prf_items.file_data",
+        // 订阅内容只保存在 subscribe 目录的文件中
+        sql: "ALTER TABLE prf_items DROP COLUMN file_data;",
+    },
+];
+
+/// 程序支持的最新数据库版本
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map_or(0, |m| m.version)
+}
+
+/// 数据库当前的版本, 没有执行过迁移时为 0
+pub async fn current_version(pool: &Pool<Sqlite>) -> Result<i64> {
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER PRIMARY KEY NOT NULL,
+            description TEXT NOT NULL,
+            applied INTEGER NOT NULL
+        )",
+    )
+    .execute(pool)
+    .await?;
+    let row = sqlx::query("SELECT COALESCE(MAX(version), 0) AS version FROM schema_version")
+        .fetch_one(pool)
+        .await?;
+    Ok(row.try_get("version")?)
+}
+
+/// 执行所有未执行的迁移, 返回迁移后的版本
+///
+/// 数据库版本高于程序支持的版本时返回错误, 避免旧版本程序破坏数据
+pub async fn migrate(pool: &Pool<Sqlite>) -> Result<i64> {
+    let current = current_version(pool).await?;
+    let latest = latest_version();
+    if current > latest {
+        return Err(eyre!(
+            "数据库版本 {current} 高于程序支持的版本 {latest}, 请升级 lazyclash"
+        ));
+    }
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        info!(
+            "数据库迁移 {}: {}",
+            migration.version, migration.description
+        );
+        let mut tx = pool.begin().await?;
+        sqlx::query(migration.sql)
+            .execute(&mut tx)
+            .await
+            .map_err(|err| eyre!("数据库迁移 {} 失败: {err}", migration.version))?;
+        sqlx::query("INSERT INTO schema_version (version, description, applied) VALUES (?, ?, ?)")
+            .bind(migration.version)
+            .bind(migration.description)
+            .bind(Utc::now().timestamp())
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+    }
+    Ok(latest)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    /// 内存数据库每个连接都是独立的, 只能使用一个连接
+    async fn memory_pool() -> Result<Pool<Sqlite>> {
+        Ok(SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await?)
+    }
+
+    #[tokio::test]
+    async fn migrate_fresh() -> Result<()> {
+        let pool = memory_pool().await?;
+        assert_eq!(migrate(&pool).await?, latest_version());
+        assert_eq!(migrate(&pool).await?, latest_version());
+        assert_eq!(current_version(&pool).await?, latest_version());
+        let pk: i64 =
+            sqlx::query("SELECT pk FROM pragma_table_info('prf_items') WHERE name = 'uid'")
+                .fetch_one(&pool)
+                .await?
+                .try_get("pk")?;
+        assert_eq!(pk, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn migrate_legacy_table() -> Result<()> {
+        let pool = memory_pool().await?;
+        sqlx::query(MIGRATIONS[0].sql).execute(&pool).await?;
+        sqlx::query("INSERT INTO prf_items (uid, name) VALUES ('a', 'first'), ('a', 'dup'), ('b', 'second')")
+            .execute(&pool)
+            .await?;
+        migrate(&pool).await?;
+        let names: Vec<String> = sqlx::query("SELECT name FROM prf_items ORDER BY uid")
+            .fetch_all(&pool)
+            .await?
+            .iter()
+            .map(|row| row.get("name"))
+            .collect();
+        assert_eq!(names, vec!["first", "second"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn refuse_newer_schema() -> Result<()> {
+        let pool = memory_pool().await?;
+        migrate(&pool).await?;
+        sqlx::query(
+            "INSERT INTO schema_version (version, description, applied) VALUES (?, 'future', 0)",
+        )
+        .bind(latest_version() + 1)
+        .execute(&pool)
+        .await?;
+        assert!(migrate(&pool).await.is_err());
+        Ok(())
+    }
+}
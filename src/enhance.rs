@@ -0,0 +1,280 @@
+use std::{fmt, path::Path};
+
+use color_eyre::{eyre::eyre, Result};
+use serde_yaml::{Mapping, Value};
+use tracing::debug;
+
+use crate::{
+    config::CoreConfig,
+    db::{self, PrfRepo},
+    prfitem::PrfItem,
+};
+
+pub mod script;
+
+/// merge 配置中的特殊字段: (字段名, 目标字段, 是否插入到前面)
+const MERGE_FIELDS: [(&str, &str, bool); 6] = [
+    ("prepend-rules", "rules", true),
+    ("append-rules", "rules", false),
+    ("prepend-proxies", "proxies", true),
+    ("append-proxies", "proxies", false),
+    ("prepend-proxy-groups", "proxy-groups", true),
+    ("append-proxy-groups", "proxy-groups", false),
+];
+
+/// 将 merge 配置应用到 config 上
+///
+/// `prepend-*`/`append-*` 字段会插入到对应列表的前面或后面,
+/// 其他字段按键深度合并, 非映射的值直接覆盖
+pub fn use_merge(merge: Mapping, mut config: Mapping) -> Mapping {
+    for (key, value) in merge {
+        let field = key
+            .as_str()
+            .and_then(|k| MERGE_FIELDS.iter().find(|(f, _, _)| *f == k));
+        match (field, value) {
+            (Some((_, target, prepend)), Value::Sequence(items)) => {
+                merge_sequence(&mut config, target, items, *prepend);
+            }
+            (Some((field, _, _)), _) => {
+                debug!("merge 字段 {field} 不是列表, 已忽略");
+            }
+            (None, value) => match config.get_mut(&key) {
+                Some(target) => deep_merge(target, value),
+                None => {
+                    config.insert(key, value);
+                }
+            },
+        }
+    }
+    config
+}
+
+fn merge_sequence(config: &mut Mapping, target: &str, items: Vec<Value>, prepend: bool) {
+    match config.get_mut(target) {
+        Some(Value::Sequence(seq)) => {
+            if prepend {
+                seq.splice(0..0, items);
+            } else {
+                seq.extend(items);
+            }
+        }
+        _ => {
+            config.insert(target.into(), Value::Sequence(items));
+        }
+    }
+}
+
+fn deep_merge(target: &mut Value, patch: Value) {
+    match (target, patch) {
+        (Value::Mapping(target), Value::Mapping(patch)) => {
+            for (key, value) in patch {
+                match target.get_mut(&key) {
+                    Some(t) => deep_merge(t, value),
+                    None => {
+                        target.insert(key, value);
+                    }
+                }
+            }
+        }
+        (target, patch) => *target = patch,
+    }
+}
+
+/// 解析 yaml 文本, 空文档视为空映射
+pub fn parse_mapping(data: &str) -> Result<Mapping> {
+    match serde_yaml::from_str::<Value>(data)? {
+        Value::Null => Ok(Mapping::new()),
+        Value::Mapping(mapping) => Ok(mapping),
+        _ => Err(eyre!("配置文件不是一个映射")),
+    }
+}
+
+/// 脚本配置运行失败, 记录出错的脚本 uid 以便在详情页展示
+#[derive(Debug)]
+pub struct ScriptError {
+    pub uid: String,
+    pub message: String,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+/// 生成运行时配置: 以 uid 对应的配置为基础, 依次应用所有 merge 配置和 script 配置
+pub async fn generate(repo: &PrfRepo, uid: &str) -> Result<Mapping> {
+    let items = repo.list().await?;
+    let base = items
+        .iter()
+        .find(|item| item.uid.as_deref() == Some(uid))
+        .ok_or_else(|| eyre!("订阅不存在: {uid}"))?;
+    if is_enhancer(base) {
+        return Err(eyre!(
+            "{} 类型的配置不能作为运行配置",
+            base.itype.as_deref().unwrap_or_default()
+        ));
+    }
+    let mut config = parse_mapping(&db::read_prf_file(base).await?)?;
+    for item in items
+        .iter()
+        .filter(|item| item.itype.as_deref() == Some("merge"))
+    {
+        let name = item.name.as_deref().unwrap_or("merge");
+        let merge = parse_mapping(&db::read_prf_file(item).await?)
+            .map_err(|err| eyre!("merge 配置 {name} 解析失败: {err}"))?;
+        debug!("应用 merge 配置: {name}");
+        config = use_merge(merge, config);
+    }
+    for item in items
+        .iter()
+        .filter(|item| item.itype.as_deref() == Some("script"))
+    {
+        let name = item.name.clone().unwrap_or("script".to_string());
+        let source = db::read_prf_file(item).await?;
+        debug!("运行 script 配置: {name}");
+        let res = tokio::task::spawn_blocking(move || {
+            script::use_script(&source, config, script::SCRIPT_TIMEOUT)
+        })
+        .await?;
+        config = res.map_err(|err| ScriptError {
+            uid: item.uid.clone().unwrap_or_default(),
+            message: format!("script 配置 {name}: {err}"),
+        })?;
+    }
+    Ok(config)
+}
+
+/// 没有配置 external-controller 时使用的地址, lazyclash 通过它通知内核重新加载配置
+pub const DEFAULT_CONTROLLER: &str = "127.0.0.1:9090";
+
+/// 应用 lazyclash 的全局内核配置, 优先级高于订阅, merge 和 script 配置
+pub fn use_overrides(core: &CoreConfig, mut config: Mapping) -> Mapping {
+    let overrides = [
+        ("mixed-port", core.mixed_port.map(Value::from)),
+        ("port", core.port.map(Value::from)),
+        ("socks-port", core.socks_port.map(Value::from)),
+        ("allow-lan", core.allow_lan.map(Value::from)),
+        (
+            "external-controller",
+            core.external_controller.clone().map(Value::from),
+        ),
+        (
+            "external-controller-unix",
+            core.external_controller_unix.clone().map(Value::from),
+        ),
+        ("secret", core.secret.clone().map(Value::from)),
+    ];
+    for (key, value) in overrides {
+        if let Some(value) = value {
+            config.insert(key.into(), value);
+        }
+    }
+    if !config.contains_key("external-controller") {
+        config.insert("external-controller".into(), DEFAULT_CONTROLLER.into());
+    }
+    config
+}
+
+/// 写入运行时配置, 先写临时文件再替换, 避免内核读到写了一半的文件
+pub async fn write_runtime(config: &Mapping, path: &Path) -> Result<String> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.exists()) {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    let data = serde_yaml::to_string(config)?;
+    let tmp = path.with_extension("yaml.tmp");
+    tokio::fs::write(&tmp, &data).await?;
+    tokio::fs::rename(&tmp, path).await?;
+    Ok(data)
+}
+
+/// merge 和 script 配置只能叠加在其他配置上
+pub fn is_enhancer(item: &PrfItem) -> bool {
+    matches!(item.itype.as_deref(), Some("merge") | Some("script"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn merge_rules_and_keys() -> Result<()> {
+        let config = parse_mapping(
+            r#"
+mixed-port: 7890
+dns:
+  enable: false
+  nameserver: [1.1.1.1]
+rules:
+  - MATCH,PROXY
+proxy-groups:
+  - name: PROXY
+"#,
+        )?;
+        let merge = parse_mapping(
+            r#"
+prepend-rules:
+  - DOMAIN-SUFFIX,corp.example,DIRECT
+append-rules:
+  - IP-CIDR,10.0.0.0/8,DIRECT
+append-proxy-groups:
+  - name: CORP
+mixed-port: 7891
+dns:
+  enable: true
+"#,
+        )?;
+        let config = use_merge(merge, config);
+        let expected = parse_mapping(
+            r#"
+mixed-port: 7891
+dns:
+  enable: true
+  nameserver: [1.1.1.1]
+rules:
+  - DOMAIN-SUFFIX,corp.example,DIRECT
+  - MATCH,PROXY
+  - IP-CIDR,10.0.0.0/8,DIRECT
+proxy-groups:
+  - name: PROXY
+  - name: CORP
+"#,
+        )?;
+        assert_eq!(config, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn overrides_take_precedence() -> Result<()> {
+        let core = CoreConfig {
+            mixed_port: Some(7897),
+            secret: Some("s3cret".to_string()),
+            ..Default::default()
+        };
+        let config = use_overrides(
+            &core,
+            parse_mapping(
+                "mixed-port: 7890
+port: 7891
+",
+            )?,
+        );
+        let expected = parse_mapping(&format!(
+            "mixed-port: 7897\nport: 7891\nsecret: s3cret\nexternal-controller: {DEFAULT_CONTROLLER}\n"
+        ))?;
+        assert_eq!(config, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn merge_missing_list() -> Result<()> {
+        let config = use_merge(
+            parse_mapping("prepend-proxies:\n  - name: a\n")?,
+            parse_mapping("")?,
+        );
+        assert_eq!(config, parse_mapping("proxies:\n  - name: a\n")?);
+        Ok(())
+    }
+}
@@ -0,0 +1,175 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use color_eyre::{eyre::eyre, Result};
+use regex::Regex;
+use rhai::{module_resolvers::DummyModuleResolver, Dynamic, Engine, EvalAltResult, Scope};
+use serde_yaml::Mapping;
+use tracing::debug;
+
+/// 脚本最长运行时间
+pub const SCRIPT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 在沙箱中运行脚本, 脚本需定义 `fn main(config)` 并返回修改后的配置
+pub fn use_script(script: &str, config: Mapping, timeout: Duration) -> Result<Mapping> {
+    let engine = sandbox_engine(timeout);
+    let ast = engine
+        .compile(script)
+        .map_err(|err| eyre!("脚本编译失败: {err}"))?;
+    let config = rhai::serde::to_dynamic(&config).map_err(|err| eyre!("配置转换失败: {err}"))?;
+    let result = engine
+        .call_fn::<Dynamic>(&mut Scope::new(), &ast, "main", (config,))
+        .map_err(|err| match *err {
+            EvalAltResult::ErrorTerminated(..) => eyre!("脚本运行超时 ({}s)", timeout.as_secs()),
+            err => eyre!("脚本运行失败: {err}"),
+        })?;
+    rhai::serde::from_dynamic::<Mapping>(&result)
+        .map_err(|err| eyre!("脚本返回值不是有效的配置: {err}"))
+}
+
+/// 只编译不运行, 用于检查编辑后的脚本是否有语法错误
+pub fn check_script(script: &str) -> Result<()> {
+    sandbox_engine(SCRIPT_TIMEOUT)
+        .compile(script)
+        .map_err(|err| eyre!("脚本编译失败: {err}"))?;
+    Ok(())
+}
+
+/// `Engine::new` 本身不提供文件和网络访问, 这里再关闭模块加载并限制资源和运行时间
+fn sandbox_engine(timeout: Duration) -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .set_module_resolver(DummyModuleResolver::new())
+        .set_max_call_levels(64)
+        .set_max_expr_depths(64, 64)
+        .set_max_string_size(16 * 1024 * 1024)
+        .set_max_array_size(100_000)
+        .set_max_map_size(100_000)
+        .disable_symbol("eval");
+    let start = Instant::now();
+    engine.on_progress(move |_| {
+        if start.elapsed() > timeout {
+            Some(Dynamic::UNIT)
+        } else {
+            None
+        }
+    });
+    engine.on_print(|s| debug!("script: {s}"));
+    engine.on_debug(|s, _, pos| debug!("script {pos:?}: {s}"));
+    let regexes = RegexCache::default();
+    let cache = regexes.clone();
+    engine.register_fn(
+        "regex_match",
+        move |text: &str, pattern: &str| -> Result<bool, Box<EvalAltResult>> {
+            Ok(cache.get(pattern)?.is_match(text))
+        },
+    );
+    engine.register_fn(
+        "regex_replace",
+        move |text: &str, pattern: &str, rep: &str| -> Result<String, Box<EvalAltResult>> {
+            Ok(regexes.get(pattern)?.replace_all(text, rep).to_string())
+        },
+    );
+    engine
+}
+
+/// 脚本通常在循环中对每个节点使用同一个正则, 编译结果按 pattern 缓存
+#[derive(Clone, Default)]
+struct RegexCache(Rc<RefCell<HashMap<String, Regex>>>);
+
+impl RegexCache {
+    /// 缓存数量上限, 超过后清空, 避免脚本拼接出大量不同的 pattern
+    const CAPACITY: usize = 256;
+
+    fn get(&self, pattern: &str) -> Result<Regex, Box<EvalAltResult>> {
+        let mut cache = self.0.borrow_mut();
+        if let Some(re) = cache.get(pattern) {
+            return Ok(re.clone());
+        }
+        let re = Regex::new(pattern).map_err(|err| err.to_string())?;
+        if cache.len() >= Self::CAPACITY {
+            cache.clear();
+        }
+        cache.insert(pattern.to_string(), re.clone());
+        Ok(re)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::enhance::parse_mapping;
+
+    #[test]
+    fn rename_and_group() -> Result<()> {
+        let config = parse_mapping(
+            r#"
+proxies:
+  - name: "HK-01 | 1x"
+  - name: "JP-01 | 2x"
+proxy-groups: []
+"#,
+        )?;
+        let script = r#"
+fn main(config) {
+    let names = [];
+    for p in config.proxies {
+        p.name = regex_replace(p.name, "\\s*\\|.*$", "");
+        names.push(p.name);
+    }
+    config.proxies = config.proxies.map(|p| {
+        p.name = regex_replace(p.name, "\\s*\\|.*$", "");
+        p
+    });
+    let hk = names.filter(|n| regex_match(n, "^HK"));
+    config["proxy-groups"].push(#{ name: "HK", type: "select", proxies: hk });
+    config
+}
+"#;
+        let config = use_script(script, config, SCRIPT_TIMEOUT)?;
+        let expected = parse_mapping(
+            r#"
+proxies:
+  - name: HK-01
+  - name: JP-01
+proxy-groups:
+  - name: HK
+    type: select
+    proxies: [HK-01]
+"#,
+        )?;
+        assert_eq!(config, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn timeout() {
+        let err = use_script(
+            "fn main(config) { loop {} }",
+            Mapping::new(),
+            Duration::from_millis(100),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("超时"));
+    }
+
+    #[test]
+    fn no_import() {
+        let script = r#"import "/etc/passwd" as m; fn main(config) { config }"#;
+        assert!(use_script(script, Mapping::new(), SCRIPT_TIMEOUT).is_err());
+    }
+
+    #[test]
+    fn regex_cache() {
+        let cache = RegexCache::default();
+        assert!(cache.get("^HK").unwrap().is_match("HK-01"));
+        assert!(cache.get("^HK").is_ok());
+        assert_eq!(cache.0.borrow().len(), 1);
+        assert!(cache.get("(").is_err());
+        assert_eq!(cache.0.borrow().len(), 1);
+    }
+}
@@ -1,18 +1,22 @@
 use clap::Parser;
-use cli::Cli;
+use cli::{Cli, Command};
 use color_eyre::Result;
 
 use crate::app::App;
 
 mod action;
 mod app;
+mod bundle;
+mod clash;
 mod cli;
 mod config;
+mod enhance;
 mod errors;
 mod logging;
 mod menu;
 mod mode;
 mod prfitem;
+mod scheduler;
 mod utils;
 mod view;
 mod db;
@@ -21,9 +25,25 @@ mod db;
 async fn main() -> Result<()> {
     crate::errors::init()?;
     crate::logging::init()?;
-    crate::db::init().await?;
-    let _ = Cli::parse();
-    let mut app = App::new()?;
+    let repo = crate::db::init().await?;
+    let cli = Cli::parse();
+    match cli.command {
+        Some(Command::Export { path }) => {
+            let count = bundle::export(&repo, &path).await?;
+            println!("已导出 {count} 个订阅到 {}", path.display());
+            return Ok(());
+        }
+        Some(Command::Import { path, mode }) => {
+            let report = bundle::import(&repo, &path, mode).await?;
+            println!("已导入 {} 个订阅", report.imported);
+            for skipped in report.skipped {
+                println!("跳过 {skipped}");
+            }
+            return Ok(());
+        }
+        None => {}
+    }
+    let mut app = App::new(repo)?;
     let res = if let Err(e) = app.run().await {
         eprint!("{} error: Something went wrong.", env!("CARGO_PKG_NAME"));
         Err(e)
@@ -1,2 +1,4 @@
+pub mod connections;
+pub mod proxies;
 pub mod subscription;
 pub mod version;
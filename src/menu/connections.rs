@@ -0,0 +1,531 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+use color_eyre::Result;
+use crossterm::event::{self, Event, KeyCode, KeyEvent};
+use ratatui::{
+    layout::{Constraint, Layout, Rect},
+    style::{
+        palette::tailwind::{BLUE, GREEN, SLATE},
+        Color, Modifier, Style, Stylize,
+    },
+    text::{Line, Span},
+    widgets::{Block, Clear, HighlightSpacing, Paragraph, Row, Table, TableState, Wrap},
+    Frame,
+};
+
+use crate::{
+    action::Action,
+    clash::controller::{self, Connection},
+    utils::{
+        help::{format_bytes, match_ranges},
+        popup_area,
+    },
+    view::View,
+};
+
+const SELECTED_STYLE: Style = Style::new().bg(SLATE.c800).add_modifier(Modifier::BOLD);
+const MUTED_FG_COLOR: Color = SLATE.c400;
+const UPLOAD_FG_COLOR: Color = BLUE.c400;
+const DOWNLOAD_FG_COLOR: Color = GREEN.c500;
+
+#[derive(Default)]
+enum Mode {
+    #[default]
+    Normal,
+    /// 输入筛选关键字
+    Search,
+    /// 确认关闭所有连接
+    Confirm,
+}
+
+/// 连接的排列顺序
+#[derive(Default, Clone, Copy, PartialEq)]
+enum Sort {
+    /// 最新的连接在前
+    #[default]
+    Time,
+    Host,
+    /// 上传速度从高到低
+    Upload,
+    /// 下载速度从高到低
+    Download,
+    Process,
+}
+
+impl Sort {
+    fn next(self) -> Self {
+        match self {
+            Self::Time => Self::Host,
+            Self::Host => Self::Upload,
+            Self::Upload => Self::Download,
+            Self::Download => Self::Process,
+            Self::Process => Self::Time,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Time => "时间",
+            Self::Host => "主机",
+            Self::Upload => "上传速度",
+            Self::Download => "下载速度",
+            Self::Process => "进程",
+        }
+    }
+}
+
+/// 连接页面, 显示内核当前的连接, 可以筛选和关闭连接
+#[derive(Default)]
+pub struct Connections {
+    focus: bool,
+    mode: Mode,
+    actions: VecDeque<Action>,
+    connections: Vec<Connection>,
+    /// 每个连接的 (上传速度, 下载速度), 单位字节每秒, 由相邻两次快照计算
+    speeds: HashMap<String, (u64, u64)>,
+    upload_total: u64,
+    download_total: u64,
+    /// 收到上一次快照的时间
+    last: Option<Instant>,
+    sort: Sort,
+    /// 筛选关键字, 匹配主机, 地址, 进程, 链路和规则
+    filter: String,
+    /// 筛选并排序后的连接在 connections 中的下标
+    visible: Vec<usize>,
+    /// 选中的是 visible 中的下标
+    state: TableState,
+}
+
+impl Connections {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn selected(&self) -> Option<&Connection> {
+        let i = *self.visible.get(self.state.selected()?)?;
+        self.connections.get(i)
+    }
+
+    fn speed(&self, id: &str) -> (u64, u64) {
+        self.speeds.get(id).copied().unwrap_or_default()
+    }
+
+    /// 更新连接, `elapsed` 为距离上一次快照的时间, 第一次快照没有速度
+    fn set_snapshot(&mut self, snapshot: controller::Connections, elapsed: Option<Duration>) {
+        let previous: HashMap<&str, (u64, u64)> = self
+            .connections
+            .iter()
+            .map(|c| (c.id.as_str(), (c.upload, c.download)))
+            .collect();
+        let secs = elapsed.map(|e| e.as_secs_f64()).filter(|&s| s > 0.0);
+        self.speeds = snapshot
+            .connections
+            .iter()
+            .filter_map(|c| {
+                let secs = secs?;
+                let (upload, download) = previous.get(c.id.as_str())?;
+                let speed =
+                    |now: u64, before: u64| (now.saturating_sub(before) as f64 / secs) as u64;
+                Some((
+                    c.id.clone(),
+                    (speed(c.upload, *upload), speed(c.download, *download)),
+                ))
+            })
+            .collect();
+        let id = self.selected().map(|c| c.id.clone());
+        self.connections = snapshot.connections;
+        self.upload_total = snapshot.upload_total;
+        self.download_total = snapshot.download_total;
+        self.apply(id);
+    }
+
+    /// 重新筛选和排序, 之前选中的连接仍然显示时保持选中
+    fn apply(&mut self, keep: Option<String>) {
+        let mut visible: Vec<usize> = (0..self.connections.len())
+            .filter(|&i| self.filter.is_empty() || self.matches(&self.connections[i]))
+            .collect();
+        let conns = &self.connections;
+        match self.sort {
+            Sort::Time => visible.sort_by(|&a, &b| conns[b].start.cmp(&conns[a].start)),
+            Sort::Host => visible.sort_by_key(|&i| host(&conns[i])),
+            Sort::Upload => visible.sort_by_key(|&i| std::cmp::Reverse(self.speed(&conns[i].id).0)),
+            Sort::Download => {
+                visible.sort_by_key(|&i| std::cmp::Reverse(self.speed(&conns[i].id).1))
+            }
+            Sort::Process => visible.sort_by_key(|&i| conns[i].metadata.process.to_lowercase()),
+        }
+        self.visible = visible;
+        let len = self.visible.len();
+        let index = keep
+            .and_then(|id| {
+                self.visible
+                    .iter()
+                    .position(|&i| self.connections[i].id == id)
+            })
+            .or_else(|| {
+                self.state
+                    .selected()
+                    .filter(|_| len > 0)
+                    .map(|i| i.min(len - 1))
+            })
+            .or_else(|| (len > 0).then_some(0));
+        self.state.select(index);
+    }
+
+    fn matches(&self, conn: &Connection) -> bool {
+        let meta = &conn.metadata;
+        let chains = conn.chains.join(" ");
+        [
+            meta.host.as_str(),
+            &meta.destination_ip,
+            &meta.process,
+            &meta.network,
+            &chains,
+            &conn.rule,
+            &conn.rule_payload,
+        ]
+        .iter()
+        .any(|text| !match_ranges(text, &self.filter).is_empty())
+    }
+
+    fn set_filter(&mut self, filter: String) {
+        let id = self.selected().map(|c| c.id.clone());
+        self.filter = filter;
+        self.apply(id);
+    }
+
+    fn select_next(&mut self) {
+        let len = self.visible.len();
+        let index = self
+            .state
+            .selected()
+            .map_or(0, |i| (i + 1).min(len.saturating_sub(1)));
+        self.state.select((len > 0).then_some(index));
+    }
+
+    fn select_previous(&mut self) {
+        let index = self.state.selected().map_or(0, |i| i.saturating_sub(1));
+        self.state
+            .select((!self.visible.is_empty()).then_some(index));
+    }
+
+    fn normal_event(&mut self, key: KeyEvent) -> Option<Event> {
+        match key.code {
+            KeyCode::Down => self.select_next(),
+            KeyCode::Up => self.select_previous(),
+            KeyCode::Char('/') => self.mode = Mode::Search,
+            KeyCode::Esc if !self.filter.is_empty() => self.set_filter(String::new()),
+            KeyCode::Char('s') => {
+                self.sort = self.sort.next();
+                let id = self.selected().map(|c| c.id.clone());
+                self.apply(id);
+            }
+            KeyCode::Char('d') | KeyCode::Delete => {
+                if let Some(conn) = self.selected() {
+                    self.actions
+                        .push_back(Action::CloseConnection(conn.id.clone()));
+                }
+            }
+            KeyCode::Char('D') => {
+                if !self.connections.is_empty() {
+                    self.mode = Mode::Confirm;
+                }
+            }
+            _ => return Some(Event::Key(key)),
+        }
+        None
+    }
+
+    fn search_event(&mut self, key: KeyEvent) -> Option<Event> {
+        match key.code {
+            KeyCode::Esc => {
+                self.set_filter(String::new());
+                self.mode = Mode::Normal;
+            }
+            KeyCode::Enter => self.mode = Mode::Normal,
+            KeyCode::Backspace => {
+                let mut filter = self.filter.clone();
+                filter.pop();
+                self.set_filter(filter);
+            }
+            KeyCode::Char(c) => {
+                let filter = format!("{}{c}", self.filter);
+                self.set_filter(filter);
+            }
+            KeyCode::Down => self.select_next(),
+            KeyCode::Up => self.select_previous(),
+            _ => {}
+        }
+        None
+    }
+
+    fn confirm_event(&mut self, key: KeyEvent) -> Option<Event> {
+        if matches!(key.code, KeyCode::Char('y') | KeyCode::Enter) {
+            self.actions.push_back(Action::CloseConnections);
+        }
+        self.mode = Mode::Normal;
+        None
+    }
+}
+
+impl View for Connections {
+    fn draw_menu(&mut self, f: &mut Frame, area: Rect) {
+        let mut b = Block::bordered().title("连接");
+        if self.focus {
+            b = b.border_style(Style::default().fg(Color::Yellow));
+        }
+        let (upload, download) = self
+            .speeds
+            .values()
+            .fold((0, 0), |(u, d), (su, sd)| (u + su, d + sd));
+        let lines = vec![
+            Line::from(format!("连接数: {}", self.connections.len())),
+            Line::from(vec![
+                Span::raw("上传: "),
+                Span::styled(format!("{}/s", format_bytes(upload)), UPLOAD_FG_COLOR),
+                Span::styled(
+                    format!("  共 {}", format_bytes(self.upload_total)),
+                    MUTED_FG_COLOR,
+                ),
+            ]),
+            Line::from(vec![
+                Span::raw("下载: "),
+                Span::styled(format!("{}/s", format_bytes(download)), DOWNLOAD_FG_COLOR),
+                Span::styled(
+                    format!("  共 {}", format_bytes(self.download_total)),
+                    MUTED_FG_COLOR,
+                ),
+            ]),
+        ];
+        f.render_widget(Paragraph::new(lines).block(b), area);
+    }
+
+    fn draw_detail(&mut self, f: &mut Frame, area: Rect) {
+        let [title_area, table_area] =
+            Layout::vertical([Constraint::Length(2), Constraint::Fill(1)]).areas(area);
+        let searching = matches!(self.mode, Mode::Search);
+        let mut title = vec![Span::styled(
+            format!("排序: {}  ", self.sort.label()),
+            MUTED_FG_COLOR,
+        )];
+        if searching || !self.filter.is_empty() {
+            title.push(Span::raw(format!(
+                "/{}{} ({}/{})  ",
+                self.filter,
+                if searching { "▏" } else { "" },
+                self.visible.len(),
+                self.connections.len()
+            )));
+        }
+        title.push(Span::styled(
+            "/ 筛选, s 排序, d 关闭连接, D 关闭所有连接",
+            MUTED_FG_COLOR,
+        ));
+        f.render_widget(Paragraph::new(Line::from(title)), title_area);
+
+        let rows: Vec<Row> = self
+            .visible
+            .iter()
+            .map(|&i| {
+                let conn = &self.connections[i];
+                let (upload, download) = self.speed(&conn.id);
+                connection_row(conn, upload, download)
+            })
+            .collect();
+        let header = Row::new(["主机", "网络", "链路", "规则", "上传", "下载", "进程"])
+            .style(Style::default().fg(MUTED_FG_COLOR));
+        let widths = [
+            Constraint::Fill(3),
+            Constraint::Length(4),
+            Constraint::Fill(2),
+            Constraint::Fill(2),
+            Constraint::Length(12),
+            Constraint::Length(12),
+            Constraint::Fill(1),
+        ];
+        let table = Table::new(rows, widths)
+            .header(header)
+            .highlight_style(SELECTED_STYLE)
+            .highlight_symbol(">")
+            .highlight_spacing(HighlightSpacing::Always);
+        f.render_stateful_widget(table, table_area, &mut self.state);
+
+        if matches!(self.mode, Mode::Confirm) {
+            let b = Block::bordered()
+                .title("关闭连接")
+                .border_style(Style::default().fg(Color::Red));
+            let area = popup_area(f.area(), 40, 20);
+            f.render_widget(Clear, area);
+            let p = Paragraph::new(format!(
+                "确认关闭所有 {} 个连接?\n\ny 确认, 其他键取消",
+                self.connections.len()
+            ))
+            .wrap(Wrap { trim: false })
+            .block(b);
+            f.render_widget(p, area);
+        }
+    }
+
+    fn handle_event(&mut self, event: Event) -> Option<Event> {
+        if let Event::Key(key) = event {
+            if key.kind != event::KeyEventKind::Press {
+                return Some(event);
+            }
+            return match self.mode {
+                Mode::Normal => self.normal_event(key),
+                Mode::Search => self.search_event(key),
+                Mode::Confirm => self.confirm_event(key),
+            };
+        }
+        Some(event)
+    }
+
+    fn is_focus(&self) -> bool {
+        self.focus
+    }
+
+    fn set_focus(&mut self) {
+        self.focus = !self.focus;
+        // 只在显示连接页面时接收连接信息, 离开页面后由 App 停止
+        if self.focus {
+            self.last = None;
+            self.actions.push_back(Action::WatchConnections);
+        } else {
+            self.mode = Mode::Normal;
+        }
+    }
+
+    fn name(&self) -> String {
+        "连接".to_string()
+    }
+
+    fn length(&self) -> u16 {
+        5
+    }
+
+    fn get_events(&mut self) -> Option<Action> {
+        self.actions.pop_front()
+    }
+
+    fn update(&mut self, action: Option<Action>) -> Result<()> {
+        if let Some(Action::ConnectionsData(snapshot)) = action {
+            let now = Instant::now();
+            let elapsed = self.last.map(|last| now - last);
+            self.last = Some(now);
+            self.set_snapshot(snapshot, elapsed);
+        }
+        Ok(())
+    }
+}
+
+/// 连接的目标, 没有域名时使用 IP
+fn host(conn: &Connection) -> String {
+    let meta = &conn.metadata;
+    let host = match meta.host.is_empty() {
+        true => &meta.destination_ip,
+        false => &meta.host,
+    };
+    format!("{host}:{}", meta.destination_port)
+}
+
+fn connection_row(conn: &Connection, upload: u64, download: u64) -> Row<'static> {
+    let meta = &conn.metadata;
+    // chains 从实际使用的节点排到最外层的策略组, 显示时反过来
+    let chain = conn
+        .chains
+        .iter()
+        .rev()
+        .cloned()
+        .collect::<Vec<_>>()
+        .join(" → ");
+    let rule = match conn.rule_payload.is_empty() {
+        true => conn.rule.clone(),
+        false => format!("{}({})", conn.rule, conn.rule_payload),
+    };
+    Row::new([
+        Line::from(host(conn)),
+        Line::from(meta.network.clone()),
+        Line::from(chain),
+        Line::from(rule),
+        Line::from(format!("{}/s", format_bytes(upload))).fg(UPLOAD_FG_COLOR),
+        Line::from(format!("{}/s", format_bytes(download))).fg(DOWNLOAD_FG_COLOR),
+        Line::from(meta.process.clone()),
+    ])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::clash::controller::Metadata;
+
+    fn conn(id: &str, host: &str, process: &str, upload: u64, download: u64) -> Connection {
+        Connection {
+            id: id.to_string(),
+            upload,
+            download,
+            start: format!("2024-10-01T00:00:0{id}Z"),
+            metadata: Metadata {
+                host: host.to_string(),
+                process: process.to_string(),
+                destination_port: "443".to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn snapshot(connections: Vec<Connection>) -> controller::Connections {
+        controller::Connections {
+            connections,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn speed_sort_and_filter() {
+        let mut view = Connections::new();
+        view.set_snapshot(
+            snapshot(vec![
+                conn("1", "a.com", "curl", 0, 0),
+                conn("2", "b.com", "firefox", 0, 0),
+                conn("3", "c.com", "firefox", 0, 0),
+            ]),
+            None,
+        );
+        assert!(view.speeds.is_empty());
+        // 默认最新的连接在前
+        assert_eq!(view.selected().map(|c| c.id.as_str()), Some("3"));
+
+        view.set_snapshot(
+            snapshot(vec![
+                conn("1", "a.com", "curl", 2048, 100),
+                conn("2", "b.com", "firefox", 0, 8192),
+                conn("3", "c.com", "firefox", 1024, 4096),
+                conn("4", "d.com", "git", 4096, 4096),
+            ]),
+            Some(Duration::from_secs(2)),
+        );
+        assert_eq!(view.speed("1"), (1024, 50));
+        assert_eq!(view.speed("2"), (0, 4096));
+        // 新连接还没有速度
+        assert_eq!(view.speed("4"), (0, 0));
+        assert_eq!(view.selected().map(|c| c.id.as_str()), Some("3"));
+
+        view.sort = Sort::Download;
+        view.apply(None);
+        let ids: Vec<&str> = view
+            .visible
+            .iter()
+            .map(|&i| view.connections[i].id.as_str())
+            .collect();
+        assert_eq!(ids, ["2", "3", "1", "4"]);
+
+        view.set_filter("FIRE".to_string());
+        assert_eq!(view.visible.len(), 2);
+        view.set_filter("d.com".to_string());
+        assert_eq!(view.selected().map(|c| c.id.as_str()), Some("4"));
+    }
+}
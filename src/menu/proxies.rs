@@ -0,0 +1,563 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use color_eyre::Result;
+use crossterm::event::{self, Event, KeyCode, KeyEvent};
+use ratatui::{
+    layout::{Constraint, Layout, Rect},
+    style::{
+        palette::tailwind::{BLUE, GREEN, ORANGE, RED, SLATE, YELLOW},
+        Color, Modifier, Style, Stylize,
+    },
+    text::{Line, Span},
+    widgets::{
+        Block, Cell, HighlightSpacing, List, ListItem, ListState, Paragraph, Row, Table, TableState,
+    },
+    Frame,
+};
+
+use crate::{
+    action::Action,
+    clash::controller::{Delay, ProxyInfo},
+    view::View,
+};
+
+const SELECTED_STYLE: Style = Style::new().bg(SLATE.c800).add_modifier(Modifier::BOLD);
+const NOW_FG_COLOR: Color = GREEN.c500;
+const MUTED_FG_COLOR: Color = SLATE.c400;
+
+/// 全局模式使用的策略组, 成员为配置中所有的节点和策略组
+const GLOBAL: &str = "GLOBAL";
+
+#[derive(Default)]
+enum Mode {
+    /// 在菜单中选择策略组
+    #[default]
+    Groups,
+    /// 在详情页中选择策略组的成员
+    Members,
+}
+
+/// 策略组成员的排列顺序
+#[derive(Default, Clone, Copy, PartialEq)]
+enum Sort {
+    /// 配置中的顺序
+    #[default]
+    Original,
+    Name,
+    /// 延迟从低到高, 超时和失败的节点在后, 没有测试过的节点在最后
+    Delay,
+}
+
+impl Sort {
+    fn next(self) -> Self {
+        match self {
+            Self::Original => Self::Name,
+            Self::Name => Self::Delay,
+            Self::Delay => Self::Original,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Original => "默认",
+            Self::Name => "名称",
+            Self::Delay => "延迟",
+        }
+    }
+}
+
+/// 代理页面, 菜单为策略组, 详情页为选中策略组的成员
+#[derive(Default)]
+pub struct Proxies {
+    focus: bool,
+    mode: Mode,
+    actions: VecDeque<Action>,
+    /// 内核中所有的节点和策略组, 以名称为键
+    proxies: HashMap<String, ProxyInfo>,
+    /// 按配置中的顺序排列的策略组名称
+    groups: Vec<String>,
+    group_state: ListState,
+    /// 选中策略组的成员, 按 sort 排列
+    members: Vec<String>,
+    member_state: TableState,
+    sort: Sort,
+    /// 本次运行中测试过的节点延迟, 比内核记录的延迟多区分了超时和失败
+    delays: HashMap<String, Delay>,
+    /// 正在测试延迟的节点
+    testing: HashSet<String>,
+}
+
+impl Proxies {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn selected_group(&self) -> Option<&ProxyInfo> {
+        let name = self.groups.get(self.group_state.selected()?)?;
+        self.proxies.get(name)
+    }
+
+    fn selected_member(&self) -> Option<&String> {
+        self.members.get(self.member_state.selected()?)
+    }
+
+    /// 节点最近一次测试的延迟, 本次运行中没有测试过时使用内核记录的延迟
+    fn delay(&self, name: &str) -> Option<Delay> {
+        self.delays.get(name).copied().or_else(|| {
+            let delay = self.proxies.get(name)?.last_delay()?;
+            // 内核把超时和失败都记录为 0
+            Some(match delay {
+                0 => Delay::Timeout,
+                delay => Delay::Ms(delay),
+            })
+        })
+    }
+
+    /// 更新节点数据, 保持选中的策略组和成员
+    fn set_proxies(&mut self, proxies: HashMap<String, ProxyInfo>) {
+        let group = self.selected_group().map(|g| g.name.clone());
+        self.groups = group_order(&proxies);
+        self.proxies = proxies;
+        let index = group
+            .and_then(|name| self.groups.iter().position(|g| *g == name))
+            .or_else(|| (!self.groups.is_empty()).then_some(0));
+        self.group_state.select(index);
+        match self.mode {
+            Mode::Members => self.sort_members(),
+            Mode::Groups => self.select_now(),
+        }
+    }
+
+    /// 按当前的排序方式重新排列成员, 保持选中的成员
+    fn sort_members(&mut self) {
+        let selected = self.selected_member().cloned();
+        let mut members = self
+            .selected_group()
+            .map(|g| g.all.clone())
+            .unwrap_or_default();
+        match self.sort {
+            Sort::Original => {}
+            Sort::Name => members.sort(),
+            // 稳定排序, 延迟相同的节点保持配置中的顺序
+            Sort::Delay => members.sort_by_key(|name| match self.delay(name) {
+                Some(Delay::Ms(delay)) => (0, delay),
+                Some(Delay::Timeout) => (1, 0),
+                Some(Delay::Failed) => (2, 0),
+                None => (3, 0),
+            }),
+        }
+        self.members = members;
+        let len = self.members.len();
+        let index = selected
+            .and_then(|name| self.members.iter().position(|m| *m == name))
+            .or_else(|| {
+                self.member_state
+                    .selected()
+                    .filter(|_| len > 0)
+                    .map(|i| i.min(len - 1))
+            });
+        self.member_state.select(index);
+    }
+
+    /// 选中策略组当前使用的成员
+    fn select_now(&mut self) {
+        self.member_state.select(None);
+        self.sort_members();
+        let now = self.selected_group().and_then(|g| g.now.clone());
+        let index = now.and_then(|now| self.members.iter().position(|m| *m == now));
+        self.member_state.select(index);
+    }
+
+    fn select_next_group(&mut self) {
+        self.group_state.select_next();
+        self.clamp_group();
+    }
+
+    fn select_previous_group(&mut self) {
+        self.group_state.select_previous();
+        self.clamp_group();
+    }
+
+    /// ListState 在渲染前不会限制下标, 这里先限制好再同步成员的选中项
+    fn clamp_group(&mut self) {
+        let len = self.groups.len();
+        let index = self
+            .group_state
+            .selected()
+            .filter(|_| len > 0)
+            .map(|i| i.min(len - 1));
+        self.group_state.select(index);
+        self.select_now();
+    }
+
+    fn select_member(&mut self, down: bool) {
+        let len = self.members.len();
+        if len == 0 {
+            return;
+        }
+        let index = match (self.member_state.selected(), down) {
+            (Some(i), true) => (i + 1).min(len - 1),
+            (Some(i), false) => i.saturating_sub(1),
+            (None, _) => 0,
+        };
+        self.member_state.select(Some(index));
+    }
+
+    /// 切换策略组到选中的成员
+    fn switch(&mut self) {
+        let (Some(group), Some(name)) = (self.selected_group(), self.selected_member()) else {
+            return;
+        };
+        if group.now.as_ref() != Some(name) {
+            self.actions
+                .push_back(Action::SelectProxy(group.name.clone(), name.clone()));
+        }
+    }
+
+    /// 测试选中策略组所有成员的延迟, 跳过正在测试的节点
+    fn test_delay(&mut self) {
+        let names: Vec<String> = self
+            .members
+            .iter()
+            .filter(|name| !self.testing.contains(*name))
+            .cloned()
+            .collect();
+        if names.is_empty() {
+            return;
+        }
+        self.testing.extend(names.iter().cloned());
+        self.actions.push_back(Action::TestDelay(names));
+    }
+
+    fn set_delay(&mut self, name: String, delay: Delay) {
+        self.testing.remove(&name);
+        let in_group = self.members.contains(&name);
+        self.delays.insert(name, delay);
+        if in_group && self.sort == Sort::Delay {
+            self.sort_members();
+        }
+    }
+
+    /// 在菜单和详情页中都可以使用的按键
+    fn common_event(&mut self, key: KeyEvent) -> Option<Event> {
+        match key.code {
+            KeyCode::Char('r') => self.actions.push_back(Action::LoadProxies),
+            KeyCode::Char('t') => self.test_delay(),
+            KeyCode::Char('s') => {
+                self.sort = self.sort.next();
+                self.sort_members();
+            }
+            _ => return Some(Event::Key(key)),
+        }
+        None
+    }
+
+    fn groups_event(&mut self, key: KeyEvent) -> Option<Event> {
+        match key.code {
+            KeyCode::Down => self.select_next_group(),
+            KeyCode::Up => self.select_previous_group(),
+            KeyCode::Enter => {
+                if !self.members.is_empty() {
+                    if self.member_state.selected().is_none() {
+                        self.member_state.select(Some(0));
+                    }
+                    self.mode = Mode::Members;
+                }
+            }
+            _ => return self.common_event(key),
+        }
+        None
+    }
+
+    fn members_event(&mut self, key: KeyEvent) -> Option<Event> {
+        match key.code {
+            KeyCode::Down => self.select_member(true),
+            KeyCode::Up => self.select_member(false),
+            KeyCode::Enter => self.switch(),
+            KeyCode::Esc => {
+                self.mode = Mode::Groups;
+                self.select_now();
+            }
+            _ => return self.common_event(key),
+        }
+        None
+    }
+}
+
+impl View for Proxies {
+    fn draw_menu(&mut self, f: &mut Frame, area: Rect) {
+        let mut b = Block::bordered().title("代理");
+        if self.focus {
+            b = b.border_style(Style::default().fg(Color::Yellow));
+        }
+        let items: Vec<ListItem> = self
+            .groups
+            .iter()
+            .filter_map(|name| self.proxies.get(name))
+            .map(|group| {
+                let mut spans = vec![Span::raw(group.name.clone())];
+                if let Some(now) = &group.now {
+                    spans.push(Span::styled(format!(" → {now}"), MUTED_FG_COLOR));
+                }
+                ListItem::new(Line::from(spans))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(b)
+            .highlight_style(SELECTED_STYLE)
+            .highlight_symbol(">")
+            .highlight_spacing(HighlightSpacing::Always);
+        f.render_stateful_widget(list, area, &mut self.group_state);
+    }
+
+    fn draw_detail(&mut self, f: &mut Frame, area: Rect) {
+        let Some(group) = self.selected_group() else {
+            let p = Paragraph::new("没有策略组, 确认内核正在运行后按 r 刷新");
+            f.render_widget(p, area);
+            return;
+        };
+        let [title_area, table_area] =
+            Layout::vertical([Constraint::Length(2), Constraint::Fill(1)]).areas(area);
+        let help = match self.mode {
+            Mode::Groups => "Enter 选择节点, t 测试延迟, s 排序, r 刷新",
+            Mode::Members => "Enter 切换, Esc 返回, t 测试延迟, s 排序, r 刷新",
+        };
+        let mut title = vec![
+            Span::styled(group.name.clone(), Style::default().bold()),
+            Span::raw(format!(" ({})  ", group.ptype)),
+            Span::styled(format!("排序: {}  ", self.sort.label()), MUTED_FG_COLOR),
+        ];
+        let testing = self
+            .members
+            .iter()
+            .filter(|name| self.testing.contains(*name))
+            .count();
+        if testing > 0 {
+            title.push(Span::styled(
+                format!(
+                    "测试中 {}/{}  ",
+                    self.members.len() - testing,
+                    self.members.len()
+                ),
+                BLUE.c400,
+            ));
+        }
+        title.push(Span::styled(help, MUTED_FG_COLOR));
+        f.render_widget(Paragraph::new(Line::from(title)), title_area);
+
+        let rows: Vec<Row> = self
+            .members
+            .iter()
+            .map(|name| {
+                let delay = match self.testing.contains(name) {
+                    true => delay_cell(None, true),
+                    false => delay_cell(self.delay(name), false),
+                };
+                member_row(name, group.now.as_deref(), self.proxies.get(name), delay)
+            })
+            .collect();
+        let header = Row::new(["", "名称", "类型", "UDP", "延迟"])
+            .style(Style::default().fg(MUTED_FG_COLOR));
+        let widths = [
+            Constraint::Length(2),
+            Constraint::Fill(1),
+            Constraint::Length(14),
+            Constraint::Length(4),
+            Constraint::Length(8),
+        ];
+        let mut table = Table::new(rows, widths)
+            .header(header)
+            .highlight_spacing(HighlightSpacing::Always);
+        if matches!(self.mode, Mode::Members) {
+            table = table.highlight_style(SELECTED_STYLE).highlight_symbol(">");
+        }
+        f.render_stateful_widget(table, table_area, &mut self.member_state);
+    }
+
+    fn handle_event(&mut self, event: Event) -> Option<Event> {
+        if let Event::Key(key) = event {
+            if key.kind != event::KeyEventKind::Press {
+                return Some(event);
+            }
+            return match self.mode {
+                Mode::Groups => self.groups_event(key),
+                Mode::Members => self.members_event(key),
+            };
+        }
+        Some(event)
+    }
+
+    fn is_focus(&self) -> bool {
+        self.focus
+    }
+
+    fn set_focus(&mut self) {
+        self.focus = !self.focus;
+        // 切换到代理页面时刷新, 节点可能已在其他地方切换
+        if self.focus {
+            self.actions.push_back(Action::LoadProxies);
+        } else {
+            self.mode = Mode::Groups;
+        }
+    }
+
+    fn name(&self) -> String {
+        "代理".to_string()
+    }
+
+    fn length(&self) -> u16 {
+        20
+    }
+
+    fn get_events(&mut self) -> Option<Action> {
+        self.actions.pop_front()
+    }
+
+    fn update(&mut self, action: Option<Action>) -> Result<()> {
+        match action {
+            Some(Action::ProxiesData(proxies)) => self.set_proxies(proxies),
+            Some(Action::DelayResult(name, delay)) => self.set_delay(name, delay),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// 延迟按快慢显示不同颜色, 超时和失败分别显示
+fn delay_cell(delay: Option<Delay>, testing: bool) -> Cell<'static> {
+    if testing {
+        return Cell::from("测试中").fg(BLUE.c400);
+    }
+    match delay {
+        None => Cell::from("-").fg(MUTED_FG_COLOR),
+        Some(Delay::Ms(delay)) => {
+            let color = match delay {
+                0..300 => GREEN.c500,
+                300..800 => YELLOW.c400,
+                _ => ORANGE.c500,
+            };
+            Cell::from(format!("{delay} ms")).fg(color)
+        }
+        Some(Delay::Timeout) => Cell::from("超时").fg(RED.c500),
+        Some(Delay::Failed) => Cell::from("失败").fg(RED.c800),
+    }
+}
+
+/// 策略组成员的一行, 当前使用的成员前显示标记
+fn member_row(
+    name: &str,
+    now: Option<&str>,
+    info: Option<&ProxyInfo>,
+    delay: Cell<'static>,
+) -> Row<'static> {
+    let (mark, style) = match now == Some(name) {
+        true => ("●", Style::default().fg(NOW_FG_COLOR)),
+        false => ("", Style::default()),
+    };
+    let ptype = info.map(|p| p.ptype.clone()).unwrap_or_default();
+    let udp = match info.is_some_and(|p| p.udp) {
+        true => "✓",
+        false => "",
+    };
+    Row::new([
+        Cell::from(mark).style(style),
+        Cell::from(name.to_string()).style(style),
+        Cell::from(ptype).style(style),
+        Cell::from(udp).style(style),
+        delay,
+    ])
+}
+
+/// 策略组按 GLOBAL 中的顺序排列, 即配置文件中的顺序, GLOBAL 放在最后
+///
+/// 隐藏的策略组不显示, 内核没有 GLOBAL 时按名称排列
+fn group_order(proxies: &HashMap<String, ProxyInfo>) -> Vec<String> {
+    let is_group = |name: &String| {
+        proxies
+            .get(name)
+            .is_some_and(|p| p.is_group() && !p.hidden && name != GLOBAL)
+    };
+    let mut groups: Vec<String> = match proxies.get(GLOBAL) {
+        Some(global) => global.all.iter().filter(|n| is_group(n)).cloned().collect(),
+        None => Vec::new(),
+    };
+    let mut rest: Vec<String> = proxies
+        .keys()
+        .filter(|n| is_group(n) && !groups.contains(n))
+        .cloned()
+        .collect();
+    rest.sort();
+    groups.extend(rest);
+    if proxies.contains_key(GLOBAL) {
+        groups.push(GLOBAL.to_string());
+    }
+    groups
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn proxy(name: &str, all: &[&str]) -> (String, ProxyInfo) {
+        let info = ProxyInfo {
+            name: name.to_string(),
+            all: all.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        };
+        (name.to_string(), info)
+    }
+
+    #[test]
+    fn groups_follow_global_order() {
+        let mut proxies: HashMap<String, ProxyInfo> = [
+            proxy(GLOBAL, &["Proxy", "HK", "Auto", "DIRECT"]),
+            proxy("Auto", &["HK"]),
+            proxy("Proxy", &["Auto", "HK"]),
+            proxy("Streaming", &["HK"]),
+            proxy("HK", &[]),
+            proxy("DIRECT", &[]),
+        ]
+        .into_iter()
+        .collect();
+        proxies.get_mut("Streaming").unwrap().hidden = true;
+        assert_eq!(group_order(&proxies), ["Proxy", "Auto", GLOBAL]);
+
+        proxies.remove(GLOBAL);
+        proxies.get_mut("Streaming").unwrap().hidden = false;
+        assert_eq!(group_order(&proxies), ["Auto", "Proxy", "Streaming"]);
+    }
+
+    #[test]
+    fn sort_by_delay_keeps_selection() {
+        let mut view = Proxies::new();
+        view.set_proxies(
+            [
+                proxy("Proxy", &["c", "a", "d", "b", "e"]),
+                proxy("a", &[]),
+                proxy("b", &[]),
+                proxy("c", &[]),
+                proxy("d", &[]),
+                proxy("e", &[]),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        view.mode = Mode::Members;
+        view.member_state.select(Some(1));
+        view.sort = Sort::Delay;
+        view.test_delay();
+        assert_eq!(view.testing.len(), 5);
+        // 结果逐个到达时重新排列, 选中的节点不变
+        view.set_delay("b".to_string(), Delay::Timeout);
+        view.set_delay("d".to_string(), Delay::Ms(300));
+        view.set_delay("e".to_string(), Delay::Failed);
+        view.set_delay("c".to_string(), Delay::Ms(80));
+        assert_eq!(view.members, ["c", "d", "b", "e", "a"]);
+        assert_eq!(view.selected_member().map(String::as_str), Some("a"));
+        assert_eq!(view.testing.iter().collect::<Vec<_>>(), ["a"]);
+
+        view.sort = view.sort.next();
+        view.sort_members();
+        assert_eq!(view.members, ["c", "a", "d", "b", "e"]);
+    }
+}
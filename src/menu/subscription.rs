@@ -1,44 +1,170 @@
-use std::{clone, collections::VecDeque};
+use std::{
+    clone,
+    collections::{HashMap, VecDeque},
+};
 
-use crate::{action::Action, prfitem::PrfItem, utils::popup_area, view::View};
+use crate::{
+    action::Action,
+    prfitem::{PrfItem, PrfOption},
+    utils::{
+        help::{self, match_ranges},
+        popup_area,
+        viewer::YamlViewer,
+    },
+    view::View,
+};
+use chrono::{DateTime, Local, Utc};
 use color_eyre::Result;
 use crossterm::event::{self, Event, KeyCode, KeyEvent};
 use ratatui::{
-    layout::Rect,
+    layout::{Constraint, Layout, Rect},
     style::{
-        palette::tailwind::{BLUE, GREEN, SLATE},
+        palette::tailwind::{BLUE, GREEN, RED, SLATE, YELLOW},
         Color, Modifier, Style, Stylize,
     },
-    text::Line,
-    widgets::{Block, Clear, HighlightSpacing, List, ListItem, ListState, Paragraph},
+    text::{Line, Span},
+    widgets::{Block, Clear, Gauge, HighlightSpacing, List, ListItem, ListState, Paragraph, Wrap},
     Frame,
 };
 use ratatui_input::{Input, InputState};
 use tracing::debug;
 
+mod history;
+mod share;
+
+use history::History;
+use share::Share;
+
 const TODO_HEADER_STYLE: Style = Style::new().fg(SLATE.c100).bg(BLUE.c800);
 const NORMAL_ROW_BG: Color = SLATE.c950;
 const ALT_ROW_BG_COLOR: Color = SLATE.c900;
 const SELECTED_STYLE: Style = Style::new().bg(SLATE.c800).add_modifier(Modifier::BOLD);
 const TEXT_FG_COLOR: Color = SLATE.c200;
 const COMPLETED_TEXT_FG_COLOR: Color = GREEN.c500;
+const MATCH_STYLE: Style = Style::new().fg(SLATE.c950).bg(YELLOW.c400);
 
 #[derive(Default)]
 enum Mode {
     Input,
     #[default]
     Normal,
+    /// 等待确认操作
+    Confirm,
+    /// 查看历史版本
+    History,
+    /// 显示分享二维码
+    Share,
+    /// 输入搜索关键字
+    Search,
+    /// 在详情页中浏览配置内容
+    Viewer,
+}
+
+/// 输入框的用途
+#[derive(Default, Clone, Copy, PartialEq)]
+enum InputKind {
+    /// 新增订阅链接
+    #[default]
+    Url,
+    /// 设置选中订阅的自动更新间隔
+    Interval,
+    /// 导入本地配置文件路径
+    Local,
+    /// 新建 merge 配置的名称
+    Merge,
+    /// 新建 script 配置的名称
+    Script,
+    /// 重命名选中的配置
+    Rename,
+    /// 编辑选中订阅的 http 配置
+    Option,
+}
+
+/// 等待确认的操作, 参数为 uid 和名称
+enum Confirm {
+    /// 删除配置及其文件
+    Delete(String, String),
+    /// 新增的链接已存在, 改为更新已有订阅
+    Update(String, String),
 }
 
 #[derive(Default)]
 pub struct PrfItemList {
     items: Vec<PrfItem>,
+    /// 选中的是 visible 中的下标
     state: ListState,
+    /// 搜索关键字, 为空时显示全部订阅
+    filter: String,
+    /// 名称或链接匹配搜索关键字的订阅在 items 中的下标
+    visible: Vec<usize>,
 }
 
 impl PrfItemList {
     pub fn set_items(&mut self, items: Vec<PrfItem>) {
+        let uid = self.selected().and_then(|item| item.uid.clone());
         self.items = items;
+        self.apply_filter(uid);
+    }
+
+    pub fn set_filter(&mut self, filter: String) {
+        let uid = self.selected().and_then(|item| item.uid.clone());
+        self.filter = filter;
+        self.apply_filter(uid);
+    }
+
+    /// 重新筛选订阅, 之前选中的订阅仍然显示时保持选中
+    fn apply_filter(&mut self, keep: Option<String>) {
+        self.visible = (0..self.items.len())
+            .filter(|&i| {
+                let item = &self.items[i];
+                self.filter.is_empty()
+                    || [&item.name, &item.url]
+                        .into_iter()
+                        .flatten()
+                        .any(|text| !match_ranges(text, &self.filter).is_empty())
+            })
+            .collect();
+        let len = self.visible.len();
+        let index = keep
+            .and_then(|uid| {
+                self.visible
+                    .iter()
+                    .position(|&i| self.items[i].uid.as_ref() == Some(&uid))
+            })
+            .or_else(|| (!self.filter.is_empty() && len > 0).then_some(0))
+            .or_else(|| {
+                self.state
+                    .selected()
+                    .filter(|_| len > 0)
+                    .map(|i| i.min(len - 1))
+            });
+        self.state.select(index);
+    }
+
+    fn selected(&self) -> Option<&PrfItem> {
+        let i = *self.visible.get(self.state.selected()?)?;
+        self.items.get(i)
+    }
+
+    /// 在筛选后的列表中移动选中项, 不超出列表范围
+    fn select_next(&mut self) {
+        let index = match self.state.selected() {
+            Some(i) => i
+                .saturating_add(1)
+                .min(self.visible.len().saturating_sub(1)),
+            None => 0,
+        };
+        self.state
+            .select((!self.visible.is_empty()).then_some(index));
+    }
+
+    fn select_previous(&mut self) {
+        let index = match self.state.selected() {
+            Some(i) => i.saturating_sub(1),
+            None => self.visible.len().saturating_sub(1),
+        };
+        self.state
+            .select((!self.visible.is_empty()).then_some(index));
     }
 }
 
@@ -49,8 +175,21 @@ pub struct SubScription {
     input_popua: bool,
     input_state: InputState,
     input_help: String,
+    input_kind: InputKind,
+    /// 输入框操作的订阅 uid
+    input_target: Option<String>,
     actions: VecDeque<Action>,
     pref: PrfItemList,
+    /// script 配置最近一次运行的错误, key 为 uid
+    script_errors: HashMap<String, String>,
+    /// 等待确认的操作
+    confirm: Option<Confirm>,
+    /// 打开的历史版本面板
+    history: Option<History>,
+    /// 选中订阅的配置文件内容 (uid, 内容), 选中时才从文件加载
+    detail: Option<(String, YamlViewer)>,
+    /// 打开的分享二维码弹窗
+    share: Option<Share>,
 }
 
 impl SubScription {
@@ -69,25 +208,269 @@ impl SubScription {
     }
 
     fn select_next(&mut self) {
-        self.pref.state.select_next();
+        self.pref.select_next();
+        self.load_detail();
     }
     fn select_previous(&mut self) {
-        self.pref.state.select_previous();
+        self.pref.select_previous();
+        self.load_detail();
+    }
+
+    /// 选中的订阅变化后加载其配置文件内容
+    fn load_detail(&mut self) {
+        let Some(uid) = self.selected_uid() else {
+            self.detail = None;
+            return;
+        };
+        if self.detail.as_ref().map(|(u, _)| u) != Some(&uid) {
+            self.detail = None;
+            self.actions.push_back(Action::LoadProfile(uid));
+        }
     }
 
     fn selected(&mut self) {
-        if let Some(i) = self.pref.state.selected() {
-            let item = &mut self.pref.items[i];
-            item.selected = Some(true);
-            self.actions
-                .push_back(Action::SelectedItem(item.uid.clone().unwrap()));
+        if let Some(uid) = self.selected_uid() {
+            // 由 App 保存为唯一选中的配置后刷新列表
+            self.actions.push_back(Action::SelectedItem(uid));
+            // 重新生成运行时配置, 旧的脚本错误不再有效
+            self.script_errors.clear();
+        }
+    }
+
+    fn selected_item(&self) -> Option<&PrfItem> {
+        self.pref.selected()
+    }
+
+    fn selected_uid(&self) -> Option<String> {
+        self.selected_item().and_then(|item| item.uid.clone())
+    }
+
+    fn refresh(&mut self) {
+        if let Some(uid) = self.selected_uid() {
+            self.actions.push_back(Action::SubScriptionRefresh(uid));
+        }
+    }
+
+    /// 打开输入框并直接进入编辑
+    fn open_input(&mut self, kind: InputKind, target: Option<String>, help: &str) {
+        self.input_kind = kind;
+        self.input_target = target;
+        self.input_state = InputState::default();
+        self.input_help = help.to_string();
+        self.input_popua = true;
+        self.mode = Mode::Input;
+    }
+
+    /// 用给定文本替换输入框内容
+    fn set_input_text(&mut self, text: &str) {
+        self.input_state = InputState::default();
+        for c in text.chars() {
+            self.input_state
+                .handle_message(KeyEvent::from(KeyCode::Char(c)).into());
+        }
+    }
+
+    fn complete_input_path(&mut self) {
+        if let Some(path) = help::complete_path(self.input_state.text()) {
+            self.set_input_text(&path);
+        }
+    }
+
+    fn close_input(&mut self) {
+        self.input_kind = InputKind::Url;
+        self.input_target = None;
+        self.input_state = InputState::default();
+        self.input_help = "输入".to_string();
+        self.input_popua = false;
+        self.mode = Mode::Normal;
+    }
+
+    fn delete(&mut self) {
+        let target = self.selected_item().and_then(|item| {
+            let name = item.name.clone().unwrap_or("config".to_string());
+            item.uid.clone().map(|uid| Confirm::Delete(uid, name))
+        });
+        if target.is_some() {
+            self.confirm = target;
+            self.mode = Mode::Confirm;
+        }
+    }
+
+    fn rename(&mut self) {
+        let Some(item) = self.selected_item() else {
+            return;
+        };
+        let uid = item.uid.clone();
+        let name = item.name.clone().unwrap_or_default();
+        self.open_input(InputKind::Rename, uid, "新名称, 按 Esc 取消");
+        self.set_input_text(&name);
+    }
+
+    fn edit_option(&mut self) {
+        let Some(item) = self
+            .selected_item()
+            .filter(|item| item.itype.as_deref() == Some("remote"))
+        else {
+            return;
+        };
+        let uid = item.uid.clone();
+        let text = item
+            .option
+            .as_ref()
+            .map(PrfOption::to_text)
+            .unwrap_or_default();
+        self.open_input(
+            InputKind::Option,
+            uid,
+            "http 配置, 如 user_agent=clash.meta; timeout=30; tls_verify=true; proxy=system; header.X-Token=abc, 按 Esc 取消",
+        );
+        self.set_input_text(&text);
+    }
+
+    fn open_history(&mut self) {
+        if let Some(uid) = self.selected_uid() {
+            self.actions.push_back(Action::LoadRevisions(uid.clone()));
+            self.history = Some(History::new(uid));
+            self.mode = Mode::History;
+        }
+    }
+
+    fn open_share(&mut self) {
+        let Some(item) = self.selected_item() else {
+            return;
+        };
+        let data = self
+            .detail
+            .as_ref()
+            .filter(|(uid, _)| item.uid.as_ref() == Some(uid))
+            .map(|(_, viewer)| viewer.text());
+        match Share::new(item, data) {
+            Some(share) => {
+                self.share = Some(share);
+                self.mode = Mode::Share;
+            }
+            None => self
+                .actions
+                .push_back(Action::Warning("没有可以分享的链接".to_string())),
+        }
+    }
+
+    pub fn share_event(&mut self, key: KeyEvent) -> Option<Event> {
+        let Some(share) = self.share.as_mut() else {
+            self.mode = Mode::Normal;
+            return None;
+        };
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('c') => {
+                self.share = None;
+                self.mode = Mode::Normal;
+            }
+            KeyCode::Down => share.select_next(),
+            KeyCode::Up => share.select_previous(),
+            _ => {}
+        }
+        None
+    }
+
+    /// 输入搜索关键字时实时筛选, Enter 保留筛选结果, Esc 清除
+    pub fn search_event(&mut self, key: KeyEvent) -> Option<Event> {
+        let mut filter = self.pref.filter.clone();
+        match key.code {
+            KeyCode::Esc => {
+                filter.clear();
+                self.mode = Mode::Normal;
+            }
+            KeyCode::Enter => self.mode = Mode::Normal,
+            KeyCode::Backspace => {
+                filter.pop();
+            }
+            KeyCode::Char(c) => filter.push(c),
+            KeyCode::Down => self.select_next(),
+            KeyCode::Up => self.select_previous(),
+            _ => {}
+        }
+        if filter != self.pref.filter {
+            self.pref.set_filter(filter);
+            self.load_detail();
         }
+        None
+    }
+
+    /// 查看器没有处理的按键中 Esc 和 v 返回列表, 其他交给上层
+    pub fn viewer_event(&mut self, key: KeyEvent) -> Option<Event> {
+        let Some((_, viewer)) = self.detail.as_mut() else {
+            // 内容还在加载, 退出查看器后按普通模式处理按键
+            self.mode = Mode::Normal;
+            return match key.code {
+                KeyCode::Esc | KeyCode::Char('v') => None,
+                _ => self.normal_event(key),
+            };
+        };
+        if viewer.handle_key(key) {
+            return None;
+        }
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('v') => {
+                self.mode = Mode::Normal;
+                None
+            }
+            _ => Some(Event::Key(key)),
+        }
+    }
+
+    pub fn history_event(&mut self, key: KeyEvent) -> Option<Event> {
+        let Some(history) = self.history.as_mut() else {
+            self.mode = Mode::Normal;
+            return None;
+        };
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('h') => {
+                self.history = None;
+                self.mode = Mode::Normal;
+            }
+            KeyCode::Down => history.select_next(),
+            KeyCode::Up => history.select_previous(),
+            KeyCode::Char(' ') => history.toggle_base(),
+            KeyCode::PageDown => history.scroll_down(10),
+            KeyCode::PageUp => history.scroll_up(10),
+            KeyCode::Char('r') => {
+                if let Some(revision) = history.selected_revision() {
+                    let uid = history.uid.clone();
+                    self.actions
+                        .push_back(Action::RollbackProfile(uid.clone(), revision.id));
+                    self.actions.push_back(Action::LoadRevisions(uid));
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+
+    pub fn confirm_event(&mut self, key: KeyEvent) -> Option<Event> {
+        let confirm = self.confirm.take();
+        if matches!(key.code, KeyCode::Char('y') | KeyCode::Enter) {
+            match confirm {
+                Some(Confirm::Delete(uid, _)) => {
+                    self.script_errors.remove(&uid);
+                    self.actions.push_back(Action::DeleteProfile(uid));
+                }
+                Some(Confirm::Update(uid, _)) => {
+                    self.actions.push_back(Action::SubScriptionRefresh(uid));
+                }
+                None => {}
+            }
+        }
+        self.mode = Mode::Normal;
+        None
     }
 
     pub fn normal_event(&mut self, key: KeyEvent) -> Option<Event> {
         match key.code {
             KeyCode::Char('a') => {
                 debug!("a");
+                if self.input_kind != InputKind::Url {
+                    self.close_input();
+                }
                 self.input_popua = !self.input_popua;
                 None
             }
@@ -96,6 +479,83 @@ impl SubScription {
                 self.mode = Mode::Input;
                 None
             }
+            KeyCode::Char('u') => {
+                self.refresh();
+                None
+            }
+            KeyCode::Char('l') => {
+                self.open_input(
+                    InputKind::Local,
+                    None,
+                    "本地配置文件路径, Tab 补全, 按 Esc 取消",
+                );
+                None
+            }
+            KeyCode::Char('m') => {
+                self.open_input(InputKind::Merge, None, "merge 配置名称, 按 Esc 取消");
+                None
+            }
+            KeyCode::Char('s') => {
+                self.open_input(InputKind::Script, None, "script 配置名称, 按 Esc 取消");
+                None
+            }
+            KeyCode::Char('d') | KeyCode::Delete => {
+                self.delete();
+                None
+            }
+            KeyCode::Char('r') => {
+                self.rename();
+                None
+            }
+            KeyCode::Char('o') => {
+                self.edit_option();
+                None
+            }
+            KeyCode::Char('h') => {
+                self.open_history();
+                None
+            }
+            KeyCode::Char('c') => {
+                self.open_share();
+                None
+            }
+            KeyCode::Char('e') => {
+                if let Some(uid) = self.selected_uid() {
+                    self.actions.push_back(Action::EditProfile(uid));
+                }
+                None
+            }
+            KeyCode::Char('/') => {
+                self.mode = Mode::Search;
+                None
+            }
+            KeyCode::Char('v') => {
+                if self.detail.is_some() {
+                    self.mode = Mode::Viewer;
+                }
+                None
+            }
+            KeyCode::PageDown | KeyCode::PageUp => {
+                if let Some((_, viewer)) = self.detail.as_mut() {
+                    viewer.handle_key(key);
+                }
+                None
+            }
+            KeyCode::Esc if !self.pref.filter.is_empty() => {
+                self.pref.set_filter(String::new());
+                self.load_detail();
+                None
+            }
+            KeyCode::Char('t') => {
+                if let Some(uid) = self.selected_uid() {
+                    self.open_input(
+                        InputKind::Interval,
+                        Some(uid),
+                        "更新间隔(分钟), 0 为不自动更新, 按 Esc 取消",
+                    );
+                }
+                None
+            }
             KeyCode::Down => {
                 self.select_next();
                 None
@@ -104,7 +564,7 @@ impl SubScription {
                 self.select_previous();
                 None
             }
-            KeyCode::Backspace => {
+            KeyCode::Enter | KeyCode::Backspace => {
                 self.selected();
                 None
             }
@@ -116,14 +576,68 @@ impl SubScription {
         debug!("subscripiton:{:?}", key.code);
         match key.code {
             KeyCode::Esc => {
+                if self.input_kind != InputKind::Url {
+                    self.close_input();
+                    return None;
+                }
                 self.mode = Mode::Normal;
                 self.input_help = "输入".to_string();
                 None
             }
             KeyCode::Enter => {
                 debug!("enter");
-                self.actions
-                    .push_back(Action::SubScription(self.input_state.text().to_string()));
+                match self.input_kind {
+                    InputKind::Url => self
+                        .actions
+                        .push_back(Action::SubScription(self.input_state.text().to_string())),
+                    InputKind::Interval => match self.input_state.text().trim().parse::<u64>() {
+                        Ok(interval) => {
+                            if let Some(uid) = self.input_target.clone() {
+                                self.actions
+                                    .push_back(Action::SetUpdateInterval(uid, interval));
+                            }
+                            self.close_input();
+                        }
+                        Err(_) => self.input_help = "请输入整数分钟数".to_string(),
+                    },
+                    InputKind::Local => {
+                        self.actions
+                            .push_back(Action::LocalProfile(self.input_state.text().to_string()));
+                        self.close_input();
+                    }
+                    InputKind::Merge => {
+                        self.actions
+                            .push_back(Action::MergeProfile(self.input_state.text().to_string()));
+                        self.close_input();
+                    }
+                    InputKind::Script => {
+                        self.actions
+                            .push_back(Action::ScriptProfile(self.input_state.text().to_string()));
+                        self.close_input();
+                    }
+                    InputKind::Rename => {
+                        if let Some(uid) = self.input_target.clone() {
+                            self.actions.push_back(Action::RenameProfile(
+                                uid,
+                                self.input_state.text().to_string(),
+                            ));
+                        }
+                        self.close_input();
+                    }
+                    InputKind::Option => match PrfOption::parse(self.input_state.text()) {
+                        Ok(option) => {
+                            if let Some(uid) = self.input_target.clone() {
+                                self.actions.push_back(Action::SetPrfOption(uid, option));
+                            }
+                            self.close_input();
+                        }
+                        Err(err) => self.input_help = format!("{err}, 按 Esc 取消"),
+                    },
+                }
+                None
+            }
+            KeyCode::Tab if self.input_kind == InputKind::Local => {
+                self.complete_input_path();
                 None
             }
             _ => {
@@ -136,18 +650,29 @@ impl SubScription {
 
 impl View for SubScription {
     fn draw_menu(&mut self, f: &mut Frame, area: Rect) {
-        let mut b = Block::bordered().title("订阅");
+        let searching = matches!(self.mode, Mode::Search);
+        let title = match !searching && self.pref.filter.is_empty() {
+            true => "订阅".to_string(),
+            false => format!(
+                "订阅 /{}{} ({}/{})",
+                self.pref.filter,
+                if searching { "▏" } else { "" },
+                self.pref.visible.len(),
+                self.pref.items.len()
+            ),
+        };
+        let mut b = Block::bordered().title(title);
         if self.focus {
             b = b.border_style(Style::default().fg(Color::Yellow));
         }
         let items: Vec<ListItem> = self
             .pref
-            .items
+            .visible
             .iter()
             .enumerate()
-            .map(|(i, todo_item)| {
+            .map(|(i, &index)| {
                 let color = alternate_colors(i);
-                ListItem::from(todo_item).bg(color)
+                prf_list_item(&self.pref.items[index], &self.pref.filter).bg(color)
             })
             .collect();
         let list = List::new(items)
@@ -160,6 +685,41 @@ impl View for SubScription {
     }
 
     fn draw_detail(&mut self, f: &mut Frame, area: Rect) {
+        if let Some(item) = self.pref.selected() {
+            let [info_area, mut area] =
+                Layout::vertical([Constraint::Length(7), Constraint::Fill(1)]).areas(area);
+            draw_info(f, info_area, item);
+            if let Some(err) = item
+                .uid
+                .as_ref()
+                .and_then(|uid| self.script_errors.get(uid))
+            {
+                let [err_area, rest] =
+                    Layout::vertical([Constraint::Length(5), Constraint::Fill(1)]).areas(area);
+                let b = Block::bordered()
+                    .title("脚本错误")
+                    .border_style(Style::default().fg(Color::Red));
+                let p = Paragraph::new(err.clone())
+                    .wrap(Wrap { trim: false })
+                    .block(b);
+                f.render_widget(p, err_area);
+                area = rest;
+            }
+            match self
+                .history
+                .as_mut()
+                .filter(|history| item.uid.as_ref() == Some(&history.uid))
+            {
+                Some(history) => history.draw(f, area),
+                None => match self.detail.as_mut() {
+                    Some((uid, viewer)) if item.uid.as_ref() == Some(uid) => {
+                        viewer.draw(f, area, matches!(self.mode, Mode::Viewer))
+                    }
+                    _ => f.render_widget(Paragraph::new("加载中..."), area),
+                },
+            }
+        }
+        // 弹窗最后绘制, 避免被详情内容覆盖
         if self.input_popua {
             let b = Block::bordered().title(self.input_help.clone());
             let area = popup_area(f.area(), 60, 10);
@@ -169,18 +729,30 @@ impl View for SubScription {
             let inner_area = b.inner(area);
             f.render_stateful_widget(input, inner_area, &mut self.input_state);
         }
-        if self.pref.state.selected().is_none() {
-            return;
-        }
-        let item = self.pref.items.get(self.pref.state.selected().unwrap());
-        if item.is_some() {
-            let item = item.unwrap();
-            let p = Paragraph::new(format!(
-                "{}",
-                item.file_data.clone().unwrap_or("没有信息".to_string())
-            ));
+        if let Some(confirm) = &self.confirm {
+            let (title, color, text) = match confirm {
+                Confirm::Delete(_, name) => {
+                    ("删除", Color::Red, format!("确认删除 {name} 及其配置文件?"))
+                }
+                Confirm::Update(_, name) => (
+                    "订阅已存在",
+                    Color::Yellow,
+                    format!("该链接已添加为订阅 {name}, 是否更新该订阅?"),
+                ),
+            };
+            let b = Block::bordered()
+                .title(title)
+                .border_style(Style::default().fg(color));
+            let area = popup_area(f.area(), 40, 20);
+            f.render_widget(Clear, area);
+            let p = Paragraph::new(format!("{text}\n\ny 确认, 其他键取消"))
+                .wrap(Wrap { trim: false })
+                .block(b);
             f.render_widget(p, area);
         }
+        if let Some(share) = self.share.as_mut() {
+            share.draw(f);
+        }
     }
 
     fn handle_event(&mut self, event: Event) -> Option<Event> {
@@ -191,6 +763,11 @@ impl View for SubScription {
             let handle_event = match self.mode {
                 Mode::Normal => self.normal_event(key),
                 Mode::Input => self.input_event(key),
+                Mode::Confirm => self.confirm_event(key),
+                Mode::History => self.history_event(key),
+                Mode::Share => self.share_event(key),
+                Mode::Search => self.search_event(key),
+                Mode::Viewer => self.viewer_event(key),
             };
             return handle_event;
         }
@@ -224,7 +801,46 @@ impl View for SubScription {
 
         match action.unwrap() {
             Action::UpdatePrfList(items) => {
+                let before = self
+                    .selected_item()
+                    .map(|item| (item.uid.clone(), item.updated));
                 self.pref.set_items(items);
+                let after = self
+                    .selected_item()
+                    .map(|item| (item.uid.clone(), item.updated));
+                // 选中的订阅更新后内容可能已经变化, 重新加载
+                // 没有变化时保留查看器的滚动, 折叠和搜索状态
+                if before != after {
+                    self.detail = None;
+                }
+                self.load_detail();
+                Ok(())
+            }
+            Action::SubScriptionResult(_) => {
+                // 新增或刷新订阅后重新加载列表
+                self.actions.push_back(Action::SubScriptionUpdate);
+                Ok(())
+            }
+            Action::SubScriptionExists(uid, name) => {
+                self.close_input();
+                self.confirm = Some(Confirm::Update(uid, name));
+                self.mode = Mode::Confirm;
+                Ok(())
+            }
+            Action::Revisions(uid, revisions) => {
+                if let Some(history) = self.history.as_mut().filter(|h| h.uid == uid) {
+                    history.set_revisions(revisions);
+                }
+                Ok(())
+            }
+            Action::ProfileData(uid, data) => {
+                if self.selected_uid().as_ref() == Some(&uid) {
+                    self.detail = Some((uid, YamlViewer::new(data)));
+                }
+                Ok(())
+            }
+            Action::ScriptError(uid, err) => {
+                self.script_errors.insert(uid, err);
                 Ok(())
             }
             _ => Ok(()),
@@ -232,19 +848,114 @@ impl View for SubScription {
     }
 }
 
-impl From<&PrfItem> for ListItem<'_> {
-    fn from(value: &PrfItem) -> Self {
-        let line = match value.selected.map_or(false, |f| f) {
-            false => Line::styled(
-                format!(" ☐ {}", value.name.as_ref().map_or("config", |f| &f)),
-                TEXT_FG_COLOR,
-            ),
-            true => Line::styled(
-                format!(" ✓ {}", value.name.as_ref().map_or("config", |f| &f)),
-                COMPLETED_TEXT_FG_COLOR,
-            ),
+/// 订阅列表中的一项, 高亮匹配搜索关键字的部分, 只有链接匹配时在下一行显示链接
+fn prf_list_item(item: &PrfItem, filter: &str) -> ListItem<'static> {
+    let (mark, color) = match item.selected.unwrap_or(false) {
+        false => (" ☐ ", TEXT_FG_COLOR),
+        true => (" ✓ ", COMPLETED_TEXT_FG_COLOR),
+    };
+    let name = item.name.as_deref().unwrap_or("config");
+    let mut spans = vec![Span::styled(mark, color)];
+    spans.extend(highlight(name, filter, Style::default().fg(color)));
+    let mut lines = vec![Line::from(spans)];
+    if let Some(url) = item
+        .url
+        .as_deref()
+        .filter(|_| !filter.is_empty() && match_ranges(name, filter).is_empty())
+    {
+        let mut spans = vec![Span::raw("   ")];
+        spans.extend(highlight(url, filter, Style::default().fg(SLATE.c500)));
+        lines.push(Line::from(spans));
+    }
+    ListItem::new(lines)
+}
+
+/// 将文本按匹配位置拆分, 匹配的部分使用 MATCH_STYLE
+fn highlight(text: &str, filter: &str, style: Style) -> Vec<Span<'static>> {
+    let mut spans = Vec::new();
+    let mut last = 0;
+    for range in match_ranges(text, filter) {
+        if range.start > last {
+            spans.push(Span::styled(text[last..range.start].to_string(), style));
+        }
+        spans.push(Span::styled(text[range.clone()].to_string(), MATCH_STYLE));
+        last = range.end;
+    }
+    if last < text.len() {
+        spans.push(Span::styled(text[last..].to_string(), style));
+    }
+    spans
+}
+
+/// 绘制订阅信息: 类型, 更新时间, 流量和到期时间
+fn draw_info(f: &mut Frame, area: Rect, item: &PrfItem) {
+    let b = Block::bordered().title("订阅信息");
+    let inner = b.inner(area);
+    f.render_widget(b, area);
+    let [text_area, gauge_area] =
+        Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]).areas(inner);
+
+    let updated = item
+        .updated
+        .and_then(|t| DateTime::from_timestamp(t as i64, 0))
+        .map(|t| t.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or("-".to_string());
+    let mut lines = vec![Line::from(format!(
+        "类型: {}    更新于: {updated}",
+        item.itype.as_deref().unwrap_or("-")
+    ))];
+    if let Some(url) = &item.url {
+        lines.push(Line::from(format!("链接: {url}")));
+    }
+    let Some(extra) = item.extra else {
+        lines.push(Line::from("没有流量信息"));
+        f.render_widget(Paragraph::new(lines), text_area);
+        return;
+    };
+
+    let used = extra.upload + extra.download;
+    let total = match extra.total {
+        0 => "不限".to_string(),
+        total => help::format_bytes(total),
+    };
+    lines.push(Line::from(format!(
+        "流量: {} / {total}  (上传 {}, 下载 {})",
+        help::format_bytes(used),
+        help::format_bytes(extra.upload),
+        help::format_bytes(extra.download)
+    )));
+    lines.push(expire_line(extra.expire, Utc::now().timestamp()));
+    f.render_widget(Paragraph::new(lines), text_area);
+
+    if extra.total > 0 {
+        let ratio = (used as f64 / extra.total as f64).clamp(0.0, 1.0);
+        let color = match ratio {
+            r if r >= 0.9 => RED.c500,
+            r if r >= 0.7 => YELLOW.c500,
+            _ => GREEN.c500,
         };
-        ListItem::new(line)
+        let gauge = Gauge::default()
+            .gauge_style(Style::default().fg(color).bg(SLATE.c800))
+            .ratio(ratio)
+            .label(format!("{:.1}%", ratio * 100.0));
+        f.render_widget(gauge, gauge_area);
+    }
+}
+
+/// 到期时间和剩余天数, expire 为 0 表示长期有效
+fn expire_line(expire: u64, now: i64) -> Line<'static> {
+    if expire == 0 {
+        return Line::from("到期: 长期有效");
+    }
+    let date = DateTime::from_timestamp(expire as i64, 0)
+        .map(|t| t.with_timezone(&Local).format("%Y-%m-%d").to_string())
+        .unwrap_or("-".to_string());
+    if expire as i64 <= now {
+        return Line::styled(format!("到期: {date} (已过期)"), RED.c500);
+    }
+    match (expire as i64 - now) / 86400 {
+        d if d < 7 => Line::styled(format!("到期: {date} (剩余 {d} 天)"), YELLOW.c500),
+        d => Line::from(format!("到期: {date} (剩余 {d} 天)")),
     }
 }
 
@@ -255,3 +966,65 @@ const fn alternate_colors(i: usize) -> Color {
         ALT_ROW_BG_COLOR
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn profile(uid: &str, name: &str, url: Option<&str>) -> PrfItem {
+        PrfItem {
+            uid: Some(uid.to_string()),
+            name: Some(name.to_string()),
+            url: url.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn filter_keeps_selection() {
+        let mut list = PrfItemList::default();
+        list.set_items(vec![
+            profile("a", "home", None),
+            profile("b", "work", Some("https://example.com/sub")),
+            profile("c", "work 2", None),
+        ]);
+        list.state.select(Some(2));
+        list.set_filter("WORK".to_string());
+        assert_eq!(list.visible, vec![1, 2]);
+        assert_eq!(list.selected().and_then(|i| i.uid.as_deref()), Some("c"));
+        // 选中的订阅被过滤掉时选中第一个匹配项
+        list.set_filter("example".to_string());
+        assert_eq!(list.selected().and_then(|i| i.uid.as_deref()), Some("b"));
+        list.select_next();
+        assert_eq!(list.state.selected(), Some(0));
+        list.set_filter("none".to_string());
+        assert!(list.selected().is_none());
+        list.set_filter(String::new());
+        assert_eq!(list.visible.len(), 3);
+    }
+
+    #[test]
+    fn refresh_keeps_viewer() -> Result<()> {
+        let mut view = SubScription::new();
+        let mut item = profile("a", "home", None);
+        item.updated = Some(1);
+        view.update(Some(Action::UpdatePrfList(vec![item.clone()])))?;
+        view.pref.state.select(Some(0));
+        view.update(Some(Action::ProfileData(
+            "a".to_string(),
+            "rules: []\n".to_string(),
+        )))?;
+        assert!(view.detail.is_some());
+        // 其他订阅更新时不会重新加载
+        let other = profile("b", "work", None);
+        view.update(Some(Action::UpdatePrfList(vec![
+            item.clone(),
+            other.clone(),
+        ])))?;
+        assert!(view.detail.is_some());
+        item.updated = Some(2);
+        view.update(Some(Action::UpdatePrfList(vec![item, other])))?;
+        assert!(view.detail.is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,220 @@
+use chrono::{DateTime, Local};
+use ratatui::{
+    layout::{Constraint, Layout, Rect},
+    style::{
+        palette::tailwind::{CYAN, GREEN, RED, SLATE},
+        Modifier, Style,
+    },
+    text::Line,
+    widgets::{Block, HighlightSpacing, List, ListItem, ListState, Paragraph},
+    Frame,
+};
+use similar::{ChangeTag, TextDiff};
+
+use crate::prfitem::PrfRevision;
+
+/// 订阅的历史版本, 第一项为当前内容
+#[derive(Default)]
+pub struct History {
+    pub uid: String,
+    revisions: Vec<PrfRevision>,
+    state: ListState,
+    /// 对比的基准版本, 未标记时与下一个 (更旧的) 版本对比
+    base: Option<usize>,
+    diff: Vec<Line<'static>>,
+    scroll: u16,
+}
+
+impl History {
+    pub fn new(uid: String) -> Self {
+        Self {
+            uid,
+            ..Default::default()
+        }
+    }
+
+    /// 设置历史版本, 第一项为订阅当前的内容
+    pub fn set_revisions(&mut self, revisions: Vec<PrfRevision>) {
+        self.revisions = revisions;
+        self.base = None;
+        self.state.select(Some(0));
+        self.update_diff();
+    }
+
+    pub fn select_next(&mut self) {
+        self.state.select_next();
+        self.update_diff();
+    }
+
+    pub fn select_previous(&mut self) {
+        self.state.select_previous();
+        self.update_diff();
+    }
+
+    /// 标记或取消标记选中的版本作为对比基准
+    pub fn toggle_base(&mut self) {
+        let selected = self.selected_index();
+        self.base = match self.base {
+            Some(base) if base == selected => None,
+            _ => Some(selected),
+        };
+        self.update_diff();
+    }
+
+    pub fn scroll_down(&mut self, lines: u16) {
+        self.scroll = self.scroll.saturating_add(lines);
+    }
+
+    pub fn scroll_up(&mut self, lines: u16) {
+        self.scroll = self.scroll.saturating_sub(lines);
+    }
+
+    /// 选中的历史版本, 当前内容不算历史版本
+    pub fn selected_revision(&self) -> Option<&PrfRevision> {
+        match self.selected_index() {
+            0 => None,
+            i => self.revisions.get(i),
+        }
+    }
+
+    fn selected_index(&self) -> usize {
+        self.state
+            .selected()
+            .unwrap_or(0)
+            .min(self.revisions.len().saturating_sub(1))
+    }
+
+    fn base_index(&self) -> Option<usize> {
+        match self.base {
+            Some(base) => Some(base),
+            None => {
+                let next = self.selected_index() + 1;
+                (next < self.revisions.len()).then_some(next)
+            }
+        }
+    }
+
+    fn update_diff(&mut self) {
+        self.scroll = 0;
+        let selected = self.revisions.get(self.selected_index());
+        let base = self.base_index().and_then(|i| self.revisions.get(i));
+        self.diff = match (base, selected) {
+            (Some(base), Some(selected)) if base.id != selected.id => {
+                let mut lines = vec![Line::styled(
+                    format!("{} → {}", label(base), label(selected)),
+                    Style::default().add_modifier(Modifier::BOLD),
+                )];
+                let diff = diff_lines(&base.file_data, &selected.file_data);
+                if diff.is_empty() {
+                    lines.push(Line::from("两个版本内容相同"));
+                }
+                lines.extend(diff);
+                lines
+            }
+            (_, Some(selected)) => selected
+                .file_data
+                .lines()
+                .map(|l| Line::from(l.to_string()))
+                .collect(),
+            _ => Vec::new(),
+        };
+    }
+
+    pub fn draw(&mut self, f: &mut Frame, area: Rect) {
+        let [list_area, diff_area] =
+            Layout::horizontal([Constraint::Length(26), Constraint::Fill(1)]).areas(area);
+        let items: Vec<ListItem> = self
+            .revisions
+            .iter()
+            .enumerate()
+            .map(|(i, revision)| {
+                let mark = if self.base == Some(i) { "*" } else { " " };
+                ListItem::new(format!("{mark}{}", label(revision)))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(Block::bordered().title("历史版本"))
+            .highlight_style(Style::new().bg(SLATE.c800).add_modifier(Modifier::BOLD))
+            .highlight_symbol(">")
+            .highlight_spacing(HighlightSpacing::Always);
+        f.render_stateful_widget(list, list_area, &mut self.state);
+
+        let b = Block::bordered().title("对比 (空格标记基准, r 回滚, PgUp/PgDn 滚动, Esc 返回)");
+        let p = Paragraph::new(self.diff.clone())
+            .block(b)
+            .scroll((self.scroll, 0));
+        f.render_widget(p, diff_area);
+    }
+}
+
+fn label(revision: &PrfRevision) -> String {
+    let time = DateTime::from_timestamp(revision.updated as i64, 0)
+        .map(|t| t.with_timezone(&Local).format("%m-%d %H:%M").to_string())
+        .unwrap_or("-".to_string());
+    match revision.id {
+        0 => format!("{time} (当前)"),
+        _ => time,
+    }
+}
+
+/// 按行对比两个版本, 只保留变化附近的 3 行上下文, 内容相同时返回空
+pub fn diff_lines(old: &str, new: &str) -> Vec<Line<'static>> {
+    let diff = TextDiff::from_lines(old, new);
+    let mut lines = Vec::new();
+    for group in diff.grouped_ops(3) {
+        let (Some(first), Some(last)) = (group.first(), group.last()) else {
+            continue;
+        };
+        let old_range = first.old_range().start..last.old_range().end;
+        let new_range = first.new_range().start..last.new_range().end;
+        lines.push(Line::styled(
+            format!(
+                "@@ -{},{} +{},{} @@",
+                old_range.start + 1,
+                old_range.len(),
+                new_range.start + 1,
+                new_range.len()
+            ),
+            CYAN.c500,
+        ));
+        for op in &group {
+            for change in diff.iter_changes(op) {
+                let (sign, style) = match change.tag() {
+                    ChangeTag::Delete => ("-", Style::default().fg(RED.c500)),
+                    ChangeTag::Insert => ("+", Style::default().fg(GREEN.c500)),
+                    ChangeTag::Equal => (" ", Style::default()),
+                };
+                let text = change.value().trim_end_matches(['\r', '\n']);
+                lines.push(Line::styled(format!("{sign} {text}"), style));
+            }
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn diff_changed_lines() {
+        let old = "a: 1\nb: 2\nc: 3\n";
+        let new = "a: 1\nb: 20\nc: 3\nd: 4\n";
+        let lines: Vec<String> = diff_lines(old, new)
+            .iter()
+            .map(|line| line.to_string())
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                "@@ -1,3 +1,4 @@",
+                "  a: 1",
+                "- b: 2",
+                "+ b: 20",
+                "  c: 3",
+                "+ d: 4"
+            ]
+        );
+        assert!(diff_lines(old, old).is_empty());
+    }
+}
@@ -0,0 +1,109 @@
+use ratatui::{
+    layout::{Constraint, Layout},
+    style::{palette::tailwind::SLATE, Modifier, Style},
+    widgets::{Block, Clear, HighlightSpacing, List, ListItem, ListState, Paragraph, Wrap},
+    Frame,
+};
+
+use crate::{
+    clash::{convert, ClashConfig},
+    prfitem::PrfItem,
+    utils::{popup_area, qr::QrWidget},
+};
+
+/// 分享二维码弹窗, 可以在订阅链接和各个节点的分享链接之间切换
+#[derive(Default)]
+pub struct Share {
+    /// 可分享的链接 (名称, 链接)
+    targets: Vec<(String, String)>,
+    state: ListState,
+    /// 选中链接的二维码, 生成失败时为错误信息
+    qr: Option<Result<QrWidget, String>>,
+}
+
+impl Share {
+    /// 收集订阅链接和配置中节点的分享链接, 没有可分享的链接时返回 None
+    ///
+    /// `data` 为订阅的配置文件内容, 还没有加载时只分享订阅链接
+    pub fn new(item: &PrfItem, data: Option<&str>) -> Option<Self> {
+        let mut targets = Vec::new();
+        if let Some(url) = &item.url {
+            targets.push(("订阅链接".to_string(), url.clone()));
+        }
+        if let Some(config) = data.and_then(|data| ClashConfig::parse(data).ok()) {
+            targets.extend(config.proxies.iter().filter_map(|proxy| {
+                convert::to_uri(proxy)
+                    .ok()
+                    .map(|uri| (proxy.name.clone(), uri))
+            }));
+        }
+        if targets.is_empty() {
+            return None;
+        }
+        let mut share = Self {
+            targets,
+            ..Default::default()
+        };
+        share.state.select(Some(0));
+        share.update_qr();
+        Some(share)
+    }
+
+    pub fn select_next(&mut self) {
+        self.state.select_next();
+        self.update_qr();
+    }
+
+    pub fn select_previous(&mut self) {
+        self.state.select_previous();
+        self.update_qr();
+    }
+
+    fn selected(&self) -> Option<&(String, String)> {
+        let i = self.state.selected()?;
+        self.targets
+            .get(i.min(self.targets.len().saturating_sub(1)))
+    }
+
+    fn update_qr(&mut self) {
+        self.qr = self
+            .selected()
+            .map(|(_, link)| QrWidget::new(link).map_err(|err| err.to_string()));
+    }
+
+    pub fn draw(&mut self, f: &mut Frame) {
+        let area = popup_area(f.area(), 90, 90);
+        f.render_widget(Clear, area);
+        let b = Block::bordered().title("分享二维码 (↑↓ 切换, Esc 关闭)");
+        let inner = b.inner(area);
+        f.render_widget(b, area);
+        let [list_area, qr_area] =
+            Layout::horizontal([Constraint::Length(24), Constraint::Fill(1)]).areas(inner);
+
+        let items: Vec<ListItem> = self
+            .targets
+            .iter()
+            .map(|(name, _)| ListItem::new(name.as_str()))
+            .collect();
+        let list = List::new(items)
+            .block(Block::bordered())
+            .highlight_style(Style::new().bg(SLATE.c800).add_modifier(Modifier::BOLD))
+            .highlight_symbol(">")
+            .highlight_spacing(HighlightSpacing::Always);
+        f.render_stateful_widget(list, list_area, &mut self.state);
+
+        let [qr_area, link_area] =
+            Layout::vertical([Constraint::Fill(1), Constraint::Length(3)]).areas(qr_area);
+        match &self.qr {
+            Some(Ok(qr)) => f.render_widget(qr, qr_area),
+            Some(Err(err)) => f.render_widget(Paragraph::new(err.as_str()), qr_area),
+            None => {}
+        }
+        if let Some((_, link)) = self.selected() {
+            let p = Paragraph::new(link.as_str())
+                .style(Style::default().fg(SLATE.c400))
+                .wrap(Wrap { trim: false });
+            f.render_widget(p, link_area);
+        }
+    }
+}
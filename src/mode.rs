@@ -3,6 +3,8 @@ pub enum Mode {
     #[default]
     Version,
     Subscription,
+    Proxies,
+    Connections,
 }
 
 impl From<i32> for Mode {
@@ -10,6 +12,8 @@ impl From<i32> for Mode {
         match value {
             0 => Mode::Version,
             1 => Mode::Subscription,
+            2 => Mode::Proxies,
+            3 => Mode::Connections,
             _ => Mode::Version,
         }
     }
@@ -20,6 +24,8 @@ impl Into<i32> for Mode {
         match self {
             Self::Version => 0,
             Self::Subscription => 1,
+            Self::Proxies => 2,
+            Self::Connections => 3,
         }
     }
 }
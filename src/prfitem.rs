@@ -1,9 +1,14 @@
-use crate::utils::help;
-use anyhow::{bail, Result};
-use reqwest::StatusCode;
+use std::{collections::BTreeMap, time::Duration};
+
+use crate::{clash::convert, db, utils::help};
+use anyhow::{anyhow, bail, Result};
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    StatusCode,
+};
 use serde::{Deserialize, Serialize};
 
-use tracing::debug;
+use tracing::{debug, warn};
 
 #[derive(Debug, Clone, Deserialize, Serialize, Default)]
 pub struct PrfItem {
@@ -46,6 +51,14 @@ pub struct PrfItem {
     /// the file data
     #[serde(skip)]
     pub file_data: Option<String>,
+
+    /// auto update interval in minutes, none or 0 means never
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub update_interval: Option<u64>,
+
+    /// http options used to fetch the profile, override the global options
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub option: Option<PrfOption>,
 }
 
 #[derive(Default, Debug, Clone, Copy, Deserialize, Serialize)]
@@ -56,18 +69,180 @@ pub struct PrfExtra {
     pub expire: u64,
 }
 
-impl PrfItem {
-    /// ## Remote type
-    /// create a new item from url
-    pub async fn from_url(url: &str) -> Result<PrfItem> {
-        let mut builder = reqwest::ClientBuilder::new().use_rustls_tls().no_proxy();
+/// a previous body of the profile, saved before it is overwritten by a refresh
+#[derive(Default, Debug, Clone)]
+pub struct PrfRevision {
+    pub id: i64,
+    /// the updated time of the body
+    pub updated: usize,
+    pub file_data: String,
+}
+
+/// http options used to fetch remote profiles
+#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct PrfOption {
+    /// the user agent, default `clash.meta`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+
+    /// request timeout in seconds, default 30
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+
+    /// verify the tls certificate, default true
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls_verify: Option<bool>,
 
-        let version = "clash-verge/unknown".to_string();
+    /// upstream proxy url, `system` means the proxy from the environment,
+    /// none means fetch directly
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
 
-        builder = builder.danger_accept_invalid_certs(true);
-        builder = builder.user_agent(version);
+    /// extra http headers
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+}
+
+const DEFAULT_USER_AGENT: &str = "clash.meta";
+const DEFAULT_TIMEOUT: u64 = 30;
+
+impl PrfOption {
+    /// the options of `other` override the options of self
+    pub fn merge(&self, other: Option<&PrfOption>) -> PrfOption {
+        let Some(other) = other else {
+            return self.clone();
+        };
+        let mut headers = self.headers.clone();
+        headers.extend(other.headers.clone());
+        PrfOption {
+            user_agent: other.user_agent.clone().or(self.user_agent.clone()),
+            timeout: other.timeout.or(self.timeout),
+            tls_verify: other.tls_verify.or(self.tls_verify),
+            proxy: other.proxy.clone().or(self.proxy.clone()),
+            headers,
+        }
+    }
 
-        let resp = builder.build()?.get(url).send().await?;
+    /// build the http client with the options
+    pub fn client(&self) -> Result<reqwest::Client> {
+        let user_agent = self.user_agent.as_deref().unwrap_or(DEFAULT_USER_AGENT);
+        let timeout = self.timeout.unwrap_or(DEFAULT_TIMEOUT);
+        let mut builder = reqwest::ClientBuilder::new()
+            .use_rustls_tls()
+            .user_agent(user_agent)
+            .timeout(Duration::from_secs(timeout));
+        builder = match self.proxy.as_deref().map(str::trim) {
+            None | Some("") => builder.no_proxy(),
+            Some("system") => builder,
+            Some(proxy) => builder.proxy(reqwest::Proxy::all(proxy)?),
+        };
+        if !self.tls_verify.unwrap_or(true) {
+            warn!("tls certificate verification is disabled");
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+        let mut headers = HeaderMap::new();
+        for (key, value) in &self.headers {
+            let name = HeaderName::from_bytes(key.as_bytes())
+                .map_err(|_| anyhow!("invalid header name: {key}"))?;
+            let value =
+                HeaderValue::from_str(value).map_err(|_| anyhow!("invalid header value: {key}"))?;
+            headers.insert(name, value);
+        }
+        Ok(builder.default_headers(headers).build()?)
+    }
+
+    /// parse the options from the text edited in the input box
+    /// user_agent=clash.meta; timeout=10; tls_verify=false; proxy=http://127.0.0.1:7890; header.X-Token=abc
+    pub fn parse(text: &str) -> Result<PrfOption> {
+        let mut option = PrfOption::default();
+        for part in text.split(';').map(str::trim).filter(|s| !s.is_empty()) {
+            let Some((key, value)) = part.split_once('=') else {
+                bail!("invalid option: {part}");
+            };
+            let (key, value) = (key.trim(), value.trim().to_string());
+            match key {
+                "user_agent" => option.user_agent = Some(value),
+                "timeout" => {
+                    option.timeout = Some(
+                        value
+                            .parse()
+                            .map_err(|_| anyhow!("invalid timeout: {value}"))?,
+                    )
+                }
+                "tls_verify" => {
+                    option.tls_verify = Some(
+                        value
+                            .parse()
+                            .map_err(|_| anyhow!("invalid tls_verify: {value}"))?,
+                    )
+                }
+                "proxy" => option.proxy = Some(value),
+                _ => match key.strip_prefix("header.") {
+                    Some(name) if !name.is_empty() => {
+                        option.headers.insert(name.to_string(), value);
+                    }
+                    _ => bail!("unknown option: {key}"),
+                },
+            }
+        }
+        Ok(option)
+    }
+
+    /// format the options as the text accepted by `parse`
+    pub fn to_text(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(user_agent) = &self.user_agent {
+            parts.push(format!("user_agent={user_agent}"));
+        }
+        if let Some(timeout) = self.timeout {
+            parts.push(format!("timeout={timeout}"));
+        }
+        if let Some(tls_verify) = self.tls_verify {
+            parts.push(format!("tls_verify={tls_verify}"));
+        }
+        if let Some(proxy) = &self.proxy {
+            parts.push(format!("proxy={proxy}"));
+        }
+        for (key, value) in &self.headers {
+            parts.push(format!("header.{key}={value}"));
+        }
+        parts.join("; ")
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == PrfOption::default()
+    }
+}
+
+const MERGE_TEMPLATE: &str = "\
+# lazyclash merge profile
+# applied on top of the selected profile when the runtime config is generated
+# other keys are deep merged into the profile
+
+prepend-rules: []
+append-rules: []
+prepend-proxies: []
+append-proxies: []
+prepend-proxy-groups: []
+append-proxy-groups: []
+";
+
+const SCRIPT_TEMPLATE: &str = "\
+// lazyclash script profile
+// runs after the merge profiles when the runtime config is generated
+// no file or network access, `regex_match(text, pattern)` and
+// `regex_replace(text, pattern, rep)` are available
+
+fn main(config) {
+    config
+}
+";
+
+impl PrfItem {
+    /// ## Remote type
+    /// create a new item from url
+    pub async fn from_url(url: &str, option: &PrfOption) -> Result<PrfItem> {
+        let resp = option.client()?.get(url).send().await?;
 
         let status_code = resp.status();
         if !StatusCode::is_success(&status_code) {
@@ -115,13 +290,14 @@ impl PrfItem {
             ),
         };
         debug!("file_name: {:?}", filename);
-        // let update_interval = match header.get("profile-update-interval") {
-        //     Some(value) => match value.to_str().unwrap_or("").parse::<u64>() {
-        //         Ok(val) => Some(val * 60), // hour -> min
-        //         Err(_) => None,
-        //     },
-        //     None => None,
-        // };
+        let update_interval = match header.get("profile-update-interval") {
+            Some(value) => match value.to_str().unwrap_or("").parse::<u64>() {
+                Ok(val) => Some(val * 60), // hour -> min
+                Err(_) => None,
+            },
+            None => None,
+        };
+        debug!("update_interval: {:?}", update_interval);
 
         let home = match header.get("profile-web-page-url") {
             Some(value) => {
@@ -132,6 +308,14 @@ impl PrfItem {
         };
         let data = resp.text_with_charset("utf-8").await?;
         debug!("{data}");
+        // base64 或明文的分享链接列表转换为 clash 配置
+        let data = match convert::uri_list(&data) {
+            Some(uris) => {
+                let config = convert::uri_list_to_config(&uris).map_err(|err| anyhow!("{err}"))?;
+                serde_yaml::to_string(&config)?
+            }
+            None => data,
+        };
         Ok(PrfItem {
             uid: Some(uuid::Uuid::new_v4().to_string()),
             itype: Some("remote".to_string()),
@@ -141,21 +325,173 @@ impl PrfItem {
             selected: Some(false),
             extra,
             file: filename,
-            updated: None,
+            updated: Some(chrono::Utc::now().timestamp() as usize),
             home,
             file_data: Some(data),
+            update_interval,
+            option: None,
+        })
+    }
+
+    /// ## Local type
+    /// create a new item from a yaml file on disk
+    pub async fn from_local(path: &str) -> Result<PrfItem> {
+        let path = help::expand_home(path.trim());
+        if !path.is_file() {
+            bail!("file not found: {}", path.display());
+        }
+        let data = tokio::fs::read_to_string(&path).await?;
+        let filename = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or("Local File".into());
+        debug!("file_name: {:?}", filename);
+        Ok(PrfItem {
+            uid: Some(uuid::Uuid::new_v4().to_string()),
+            itype: Some("local".to_string()),
+            desc: Some(path.display().to_string()),
+            name: Some(filename.clone()),
+            url: None,
+            selected: Some(false),
+            extra: None,
+            file: Some(filename),
+            updated: Some(chrono::Utc::now().timestamp() as usize),
+            home: None,
+            file_data: Some(data),
+            update_interval: None,
+            option: None,
         })
     }
+
+    /// ## Merge type
+    /// create a new merge item with the template
+    pub fn from_merge(name: &str) -> Result<PrfItem> {
+        PrfItem::from_template("merge", name, MERGE_TEMPLATE)
+    }
+
+    /// ## Script type
+    /// create a new script item with the template
+    pub fn from_script(name: &str) -> Result<PrfItem> {
+        PrfItem::from_template("script", name, SCRIPT_TEMPLATE)
+    }
+
+    fn from_template(itype: &str, name: &str, template: &str) -> Result<PrfItem> {
+        let name = name.trim();
+        if name.is_empty() {
+            bail!("the {itype} profile name is empty");
+        }
+        Ok(PrfItem {
+            uid: Some(uuid::Uuid::new_v4().to_string()),
+            itype: Some(itype.to_string()),
+            desc: None,
+            name: Some(name.to_string()),
+            url: None,
+            selected: Some(false),
+            extra: None,
+            file: Some(db::file_name(name)),
+            updated: Some(chrono::Utc::now().timestamp() as usize),
+            home: None,
+            file_data: Some(template.to_string()),
+            update_interval: None,
+            option: None,
+        })
+    }
+
+    /// ## Remote type
+    /// refetch the stored url, keep uid/name/file/selected of the current item
+    /// the options of the item override the global options
+    pub async fn refresh(&self, global: &PrfOption) -> Result<PrfItem> {
+        if self.itype.as_deref() != Some("remote") {
+            bail!("only remote profile can be refreshed");
+        }
+        let url = match self.url.as_deref() {
+            Some(url) => url,
+            None => bail!("the profile has no source url"),
+        };
+        let item = PrfItem::from_url(url, &global.merge(self.option.as_ref())).await?;
+        Ok(PrfItem {
+            uid: self.uid.clone(),
+            itype: self.itype.clone(),
+            name: self.name.clone(),
+            file: self.file.clone(),
+            desc: self.desc.clone(),
+            url: self.url.clone(),
+            selected: self.selected,
+            extra: item.extra,
+            updated: Some(chrono::Utc::now().timestamp() as usize),
+            home: item.home.or(self.home.clone()),
+            file_data: item.file_data,
+            update_interval: self.update_interval.or(item.update_interval),
+            option: self.option.clone(),
+        })
+    }
+
+    /// whether the auto update interval has elapsed since the last update
+    pub fn is_due(&self, now: usize) -> bool {
+        if self.itype.as_deref() != Some("remote") {
+            return false;
+        }
+        match self.update_interval {
+            Some(interval) if interval > 0 => {
+                let updated = self.updated.unwrap_or(0);
+                now >= updated + interval as usize * 60
+            }
+            _ => false,
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use super::PrfItem;
+    use super::{PrfItem, PrfOption};
+
+    #[test]
+    fn is_due() {
+        let mut item = PrfItem {
+            itype: Some("remote".to_string()),
+            updated: Some(1000),
+            ..Default::default()
+        };
+        assert!(!item.is_due(1000 + 3600));
+        item.update_interval = Some(60);
+        assert!(!item.is_due(1000 + 3599));
+        assert!(item.is_due(1000 + 3600));
+        item.update_interval = Some(0);
+        assert!(!item.is_due(1000 + 3600));
+        item.update_interval = Some(60);
+        item.itype = Some("local".to_string());
+        assert!(!item.is_due(1000 + 3600));
+    }
+
+    #[test]
+    fn option_parse_and_merge() -> anyhow::Result<()> {
+        let text = "user_agent=clash.meta; timeout=10; tls_verify=false; header.X-Token=abc";
+        let option = PrfOption::parse(text)?;
+        assert_eq!(option.timeout, Some(10));
+        assert_eq!(option.tls_verify, Some(false));
+        assert_eq!(
+            option.headers.get("X-Token").map(String::as_str),
+            Some("abc")
+        );
+        assert_eq!(PrfOption::parse(&option.to_text())?, option);
+        assert!(PrfOption::parse("timeout=abc").is_err());
+        assert!(PrfOption::parse("unknown=1").is_err());
+
+        let global = PrfOption::parse("user_agent=ua; proxy=system; header.A=1")?;
+        let merged = global.merge(Some(&PrfOption::parse(
+            "proxy=http://127.0.0.1:7890; header.B=2",
+        )?));
+        assert_eq!(merged.user_agent.as_deref(), Some("ua"));
+        assert_eq!(merged.proxy.as_deref(), Some("http://127.0.0.1:7890"));
+        assert_eq!(merged.headers.len(), 2);
+        Ok(())
+    }
+
     #[tokio::test]
     async fn from_url() -> anyhow::Result<()> {
         let url =
             "https://sub.cloudlion.me/api/v1/client/subscribe?token=6a5a4667da647891b46dc4748422b94c";
-        PrfItem::from_url(url).await?;
+        PrfItem::from_url(url, &PrfOption::default()).await?;
         Ok(())
     }
 }
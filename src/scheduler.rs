@@ -0,0 +1,91 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use color_eyre::Result;
+use tokio::{sync::mpsc::UnboundedSender, task::JoinHandle};
+use tracing::{debug, error};
+
+use crate::{
+    action::Action,
+    clash,
+    db::{self, PrfRepo},
+    prfitem::PrfOption,
+};
+
+/// 检查订阅是否需要更新的间隔
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// 刷新指定 uid 的订阅, 结果通过 Action 返回, 校验警告通过 tx 发送
+///
+/// `fetch` 为全局 http 配置, 新内容校验失败时保留原有文件, 否则旧内容保存为历史版本
+pub async fn refresh_prf_item(
+    repo: &PrfRepo,
+    uid: &str,
+    fetch: &PrfOption,
+    tx: &UnboundedSender<Action>,
+) -> Result<Action> {
+    let Some(mut old) = repo.get(uid).await? else {
+        return Ok(Action::Error(format!("订阅不存在: {uid}")));
+    };
+    let item = match old.refresh(fetch).await {
+        Ok(item) => item,
+        Err(err) => return Ok(Action::Error(format!("{err:?}"))),
+    };
+    match clash::check_profile(&item) {
+        Ok(warnings) => {
+            for warning in warnings {
+                tx.send(Action::Warning(warning))?;
+            }
+        }
+        Err(err) => return Ok(Action::Error(format!("{err}"))),
+    }
+    // 内容有变化时保存旧版本, 以便回滚
+    old.file_data = db::read_prf_file(&old).await.ok();
+    if old.file_data != item.file_data {
+        repo.insert_revision(&old).await?;
+    }
+    db::write_prf_file(&item).await?;
+    repo.update(&item).await?;
+    Ok(Action::SubScriptionResult(item))
+}
+
+/// 启动后台任务, 定时刷新到期的订阅
+pub fn spawn(repo: PrfRepo, tx: UnboundedSender<Action>, fetch: PrfOption) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(err) = refresh_due(&repo, &fetch, &tx).await {
+                error!("自动更新订阅失败: {err:?}");
+            }
+            if tx.is_closed() {
+                break;
+            }
+        }
+    })
+}
+
+async fn refresh_due(
+    repo: &PrfRepo,
+    fetch: &PrfOption,
+    tx: &UnboundedSender<Action>,
+) -> Result<()> {
+    let now = Utc::now().timestamp() as usize;
+    for item in repo.list().await? {
+        if !item.is_due(now) {
+            continue;
+        }
+        let Some(uid) = item.uid else {
+            continue;
+        };
+        debug!("自动更新订阅: {uid}");
+        let action = match refresh_prf_item(repo, &uid, fetch, tx).await {
+            Ok(action) => action,
+            Err(err) => Action::Error(format!("{err}")),
+        };
+        if tx.send(action).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
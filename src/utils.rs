@@ -1,6 +1,8 @@
 use ratatui::layout::{Constraint, Flex, Layout, Rect};
 
 pub mod help;
+pub mod qr;
+pub mod viewer;
 
 /// helper function to create a centered rect using up certain percentage of the available rect `r`
 pub fn popup_area(area: Rect, percent_x: u16, percent_y: u16) -> Rect {
@@ -10,4 +12,3 @@ pub fn popup_area(area: Rect, percent_x: u16, percent_y: u16) -> Rect {
     let [area] = horizontal.areas(area);
     area
 }
-
@@ -1,3 +1,5 @@
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 /// parse the string
@@ -24,3 +26,228 @@ pub fn get_last_part_and_decode(url: &str) -> Option<String> {
             .to_string(),
     )
 }
+
+/// expand the leading `~` to the home directory
+pub fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+            match directories::BaseDirs::new() {
+                Some(dirs) => dirs.home_dir().join(rest.trim_start_matches('/')),
+                None => PathBuf::from(path),
+            }
+        }
+        _ => PathBuf::from(path),
+    }
+}
+
+/// complete the path with the entries on disk, return the longest common prefix
+/// of all candidates, directories end with `/`
+pub fn complete_path(input: &str) -> Option<String> {
+    let (dir, prefix) = match input.rfind('/') {
+        Some(i) => (&input[..=i], &input[i + 1..]),
+        None => ("", input),
+    };
+    let read_dir = if dir.is_empty() {
+        Path::new(".").to_path_buf()
+    } else {
+        expand_home(dir)
+    };
+    let mut candidates: Vec<String> = std::fs::read_dir(read_dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !name.starts_with(prefix) || (prefix.is_empty() && name.starts_with('.')) {
+                return None;
+            }
+            match entry.file_type().map(|t| t.is_dir()) {
+                Ok(true) => Some(format!("{name}/")),
+                _ => Some(name),
+            }
+        })
+        .collect();
+    candidates.sort();
+    let first = candidates.first()?;
+    let common = candidates.iter().skip(1).fold(first.clone(), |acc, name| {
+        acc.chars()
+            .zip(name.chars())
+            .take_while(|(a, b)| a == b)
+            .map(|(a, _)| a)
+            .collect()
+    });
+    Some(format!("{dir}{common}"))
+}
+
+/// the external editor command line, `$VISUAL` first, then `$EDITOR`, defaults to `vi`
+///
+/// the variable may contain arguments, e.g. `code --wait`, and is split like a shell does,
+/// so a path with spaces can be quoted, e.g. `"/opt/my editor/bin/edit" -w`
+pub fn editor_command(visual: Option<String>, editor: Option<String>) -> (String, Vec<String>) {
+    let command = [visual, editor]
+        .into_iter()
+        .flatten()
+        .find(|c| !c.trim().is_empty())
+        .unwrap_or("vi".to_string());
+    let mut parts = shell_words(&command).into_iter();
+    let program = parts.next().unwrap_or("vi".to_string());
+    (program, parts.collect())
+}
+
+/// split the command line into words with the POSIX shell quoting rules,
+/// an unterminated quote runs to the end of the line
+fn shell_words(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\\' => word.get_or_insert_with(String::new).extend(chars.next()),
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                word.extend(chars.by_ref().take_while(|c| *c != '\''));
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => word.push('\\'),
+                        },
+                        c => word.push(c),
+                    }
+                }
+            }
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    words
+}
+
+/// format the bytes to a human readable string, e.g. 1.5 GB
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 6] = ["B", "KB", "MB", "GB", "TB", "PB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.2} {}", UNITS[unit])
+    }
+}
+
+/// find `filter` in `text` ignoring case, returns the non-overlapping byte ranges
+pub fn match_ranges(text: &str, filter: &str) -> Vec<Range<usize>> {
+    let lower = |c: char| c.to_lowercase().next().unwrap_or(c);
+    let filter: Vec<char> = filter.chars().map(lower).collect();
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let mut ranges = Vec::new();
+    if filter.is_empty() {
+        return ranges;
+    }
+    let mut i = 0;
+    while i + filter.len() <= chars.len() {
+        let hit = chars[i..i + filter.len()]
+            .iter()
+            .zip(&filter)
+            .all(|((_, c), f)| lower(*c) == *f);
+        if !hit {
+            i += 1;
+            continue;
+        }
+        let end = chars
+            .get(i + filter.len())
+            .map_or(text.len(), |(start, _)| *start);
+        ranges.push(chars[i].0..end);
+        i += filter.len();
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn match_ignore_case() {
+        assert_eq!(match_ranges("Work HK work", "work"), vec![0..4, 8..12]);
+        assert_eq!(match_ranges("香港 Node", "港 n"), vec![3..8]);
+        assert!(match_ranges("abc", "").is_empty());
+        assert!(match_ranges("ab", "abc").is_empty());
+    }
+
+    #[test]
+    fn format_bytes_units() {
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(1023), "1023 B");
+        assert_eq!(format_bytes(1536), "1.50 KB");
+        assert_eq!(format_bytes(100 * 1024 * 1024 * 1024), "100.00 GB");
+    }
+
+    #[test]
+    fn editor_from_env() {
+        assert_eq!(editor_command(None, None), ("vi".to_string(), vec![]));
+        assert_eq!(
+            editor_command(Some(" ".to_string()), Some("code --wait".to_string())),
+            ("code".to_string(), vec!["--wait".to_string()])
+        );
+        assert_eq!(
+            editor_command(Some("nvim".to_string()), Some("nano".to_string())).0,
+            "nvim"
+        );
+    }
+
+    #[test]
+    fn editor_quoted_path() {
+        assert_eq!(
+            editor_command(
+                None,
+                Some(r#""/opt/my editor/bin/edit" --wait 'a b'"#.to_string())
+            ),
+            (
+                "/opt/my editor/bin/edit".to_string(),
+                vec!["--wait".to_string(), "a b".to_string()]
+            )
+        );
+        assert_eq!(
+            editor_command(Some(r"/opt/my\ editor/edit ''".to_string()), None),
+            ("/opt/my editor/edit".to_string(), vec![String::new()])
+        );
+    }
+
+    #[test]
+    fn complete_path_common_prefix() {
+        let dir = std::env::temp_dir().join(format!("lazyclash-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("config-a.yaml"), "").unwrap();
+        std::fs::write(dir.join("config-b.yaml"), "").unwrap();
+        let base = format!("{}/", dir.display());
+
+        assert_eq!(
+            complete_path(&format!("{base}con")),
+            Some(format!("{base}config-"))
+        );
+        assert_eq!(
+            complete_path(&format!("{base}config-a")),
+            Some(format!("{base}config-a.yaml"))
+        );
+        assert_eq!(
+            complete_path(&format!("{base}s")),
+            Some(format!("{base}sub/"))
+        );
+        assert_eq!(complete_path(&format!("{base}none")), None);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
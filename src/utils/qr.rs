@@ -0,0 +1,101 @@
+use color_eyre::{eyre::eyre, Result};
+use qrcode::{EcLevel, QrCode};
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Style},
+    widgets::{Paragraph, Widget, Wrap},
+};
+
+/// 二维码四周留白的模块数, 太窄时手机难以识别
+const QUIET_ZONE: usize = 2;
+
+/// 用 Unicode 半块字符绘制的二维码, 每个字符显示上下两个模块
+pub struct QrWidget {
+    /// 包括留白在内每行的模块, true 为深色
+    modules: Vec<Vec<bool>>,
+}
+
+impl QrWidget {
+    pub fn new(data: &str) -> Result<Self> {
+        // 屏幕上显示不会有污损, 使用最低的纠错等级让二维码尽量小
+        let code = QrCode::with_error_correction_level(data, EcLevel::L)
+            .map_err(|err| eyre!("无法生成二维码: {err}"))?;
+        let width = code.width();
+        let colors = code.to_colors();
+        let size = width + QUIET_ZONE * 2;
+        let mut modules = vec![vec![false; size]; size];
+        for (i, color) in colors.iter().enumerate() {
+            modules[i / width + QUIET_ZONE][i % width + QUIET_ZONE] = *color == qrcode::Color::Dark;
+        }
+        Ok(Self { modules })
+    }
+
+    /// 绘制需要的 (列数, 行数)
+    pub fn size(&self) -> (u16, u16) {
+        let size = self.modules.len() as u16;
+        (size, size.div_ceil(2))
+    }
+
+    /// 每两行模块合成一行半块字符
+    fn lines(&self) -> Vec<String> {
+        self.modules
+            .chunks(2)
+            .map(|rows| {
+                let top = &rows[0];
+                let bottom = rows.get(1);
+                top.iter()
+                    .enumerate()
+                    .map(|(x, &top)| {
+                        let bottom = bottom.is_some_and(|row| row[x]);
+                        match (top, bottom) {
+                            (true, true) => '█',
+                            (true, false) => '▀',
+                            (false, true) => '▄',
+                            (false, false) => ' ',
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+impl Widget for &QrWidget {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let (width, height) = self.size();
+        if width > area.width || height > area.height {
+            Paragraph::new(format!(
+                "窗口太小, 显示二维码至少需要 {width}x{height}, 请放大终端"
+            ))
+            .wrap(Wrap { trim: false })
+            .render(area, buf);
+            return;
+        }
+        // 固定为白底黑字, 不受终端配色影响
+        let style = Style::default().fg(Color::Black).bg(Color::White);
+        let x = area.x + (area.width - width) / 2;
+        let y = area.y + (area.height - height) / 2;
+        for (i, line) in self.lines().iter().enumerate() {
+            buf.set_string(x, y + i as u16, line, style);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn half_block_lines() -> Result<()> {
+        let qr = QrWidget::new("https://example.com/sub?token=abc")?;
+        let (width, height) = qr.size();
+        let lines = qr.lines();
+        assert_eq!(lines.len(), height as usize);
+        assert!(lines.iter().all(|l| l.chars().count() == width as usize));
+        // 第一行是留白, 定位图案从留白之后开始
+        assert!(lines[0].chars().all(|c| c == ' '));
+        assert!(lines[1].chars().nth(QUIET_ZONE).is_some_and(|c| c != ' '));
+        Ok(())
+    }
+}
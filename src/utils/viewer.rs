@@ -0,0 +1,478 @@
+use std::{collections::HashSet, ops::Range};
+
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    layout::{Margin, Rect},
+    style::{
+        palette::tailwind::{AMBER, CYAN, GREEN, ORANGE, PURPLE, SLATE, YELLOW},
+        Color, Modifier, Style,
+    },
+    text::{Line, Span},
+    widgets::{Block, Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState},
+    Frame,
+};
+
+use super::help::match_ranges;
+
+const KEY_STYLE: Style = Style::new().fg(CYAN.c400);
+const STRING_STYLE: Style = Style::new().fg(GREEN.c400);
+const NUMBER_STYLE: Style = Style::new().fg(PURPLE.c400);
+const BOOL_STYLE: Style = Style::new().fg(ORANGE.c400);
+const MARK_STYLE: Style = Style::new().fg(YELLOW.c500);
+const COMMENT_STYLE: Style = Style::new().fg(SLATE.c500).add_modifier(Modifier::ITALIC);
+const GUTTER_STYLE: Style = Style::new().fg(SLATE.c500);
+const CURSOR_STYLE: Style = Style::new().bg(SLATE.c800);
+const MATCH_STYLE: Style = Style::new().fg(SLATE.c950).bg(AMBER.c400);
+/// 左右滚动一次的列数
+const HSCROLL_STEP: usize = 4;
+
+/// 只读的 YAML 查看器: 语法高亮, 行号, 上下左右滚动, 搜索和折叠顶层段落
+#[derive(Default)]
+pub struct YamlViewer {
+    text: String,
+    lines: Vec<String>,
+    /// 顶层段落的行范围, 只包含多于一行的段落
+    sections: Vec<Range<usize>>,
+    /// 已折叠段落的起始行
+    folded: HashSet<usize>,
+    /// 光标所在的行
+    cursor: usize,
+    /// 第一个显示的行在可见行中的位置
+    scroll: usize,
+    hscroll: usize,
+    /// 上次绘制时的可显示行数, 用于翻页
+    height: usize,
+    /// 正在输入搜索关键字
+    searching: bool,
+    query: String,
+    /// 包含搜索关键字的行
+    matches: Vec<usize>,
+}
+
+impl YamlViewer {
+    pub fn new(text: String) -> Self {
+        // tab 会破坏列对齐, 展开为空格
+        let lines: Vec<String> = text.lines().map(|l| l.replace('\t', "    ")).collect();
+        let sections = top_level_sections(&lines);
+        Self {
+            text,
+            lines,
+            sections,
+            ..Default::default()
+        }
+    }
+
+    /// 原始文本
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// 处理按键, 返回 false 表示没有处理, 由调用方处理; 输入搜索关键字时处理所有按键
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        if self.searching {
+            self.search_key(key);
+            return true;
+        }
+        match key.code {
+            KeyCode::Down | KeyCode::Char('j') => self.move_cursor(1),
+            KeyCode::Up | KeyCode::Char('k') => self.move_cursor(-1),
+            KeyCode::PageDown => self.move_cursor(self.page() as isize),
+            KeyCode::PageUp => self.move_cursor(-(self.page() as isize)),
+            KeyCode::Home | KeyCode::Char('g') => self.cursor = 0,
+            KeyCode::End | KeyCode::Char('G') => {
+                self.cursor = self.visible_lines().last().copied().unwrap_or(0)
+            }
+            KeyCode::Char('l') => self.hscroll = self.hscroll.saturating_add(HSCROLL_STEP),
+            KeyCode::Char('h') => self.hscroll = self.hscroll.saturating_sub(HSCROLL_STEP),
+            KeyCode::Char('z') | KeyCode::Char(' ') => self.toggle_fold(),
+            KeyCode::Char('Z') => self.toggle_fold_all(),
+            KeyCode::Char('/') => {
+                self.searching = true;
+                self.query.clear();
+                self.matches.clear();
+            }
+            KeyCode::Char('n') => self.next_match(true),
+            KeyCode::Char('N') => self.next_match(false),
+            _ => return false,
+        }
+        true
+    }
+
+    /// 输入时实时跳转到光标之后的第一个匹配, Enter 确认, Esc 清除搜索
+    fn search_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Esc => {
+                self.searching = false;
+                self.query.clear();
+                self.matches.clear();
+                return;
+            }
+            KeyCode::Enter => {
+                self.searching = false;
+                return;
+            }
+            KeyCode::Backspace => {
+                self.query.pop();
+            }
+            KeyCode::Char(c) => self.query.push(c),
+            _ => return,
+        }
+        self.matches = (0..self.lines.len())
+            .filter(|&i| !match_ranges(&self.lines[i], &self.query).is_empty())
+            .collect();
+        if let Some(&line) = self.matches.iter().find(|&&l| l >= self.cursor) {
+            self.jump_to(line);
+        } else if let Some(&line) = self.matches.first() {
+            self.jump_to(line);
+        }
+    }
+
+    fn next_match(&mut self, forward: bool) {
+        let next = match forward {
+            true => self
+                .matches
+                .iter()
+                .find(|&&l| l > self.cursor)
+                .or(self.matches.first()),
+            false => self
+                .matches
+                .iter()
+                .rev()
+                .find(|&&l| l < self.cursor)
+                .or(self.matches.last()),
+        };
+        if let Some(&line) = next {
+            self.jump_to(line);
+        }
+    }
+
+    /// 移动光标到指定行, 所在段落折叠时先展开
+    fn jump_to(&mut self, line: usize) {
+        if let Some(start) = self.section_of(line).map(|s| s.start) {
+            if line != start {
+                self.folded.remove(&start);
+            }
+        }
+        self.cursor = line;
+    }
+
+    fn page(&self) -> usize {
+        self.height.max(1)
+    }
+
+    /// 在可见行中移动光标, 跳过折叠的行
+    fn move_cursor(&mut self, delta: isize) {
+        let visible = self.visible_lines();
+        let Some(last) = visible.len().checked_sub(1) else {
+            return;
+        };
+        let row = self.cursor_row(&visible);
+        let row = row.saturating_add_signed(delta).min(last);
+        self.cursor = visible[row];
+    }
+
+    fn section_of(&self, line: usize) -> Option<&Range<usize>> {
+        self.sections.iter().find(|s| s.contains(&line))
+    }
+
+    /// 折叠或展开光标所在的顶层段落
+    fn toggle_fold(&mut self) {
+        let Some(start) = self.section_of(self.cursor).map(|s| s.start) else {
+            return;
+        };
+        if !self.folded.remove(&start) {
+            self.folded.insert(start);
+            self.cursor = start;
+        }
+    }
+
+    /// 有展开的段落时全部折叠, 否则全部展开
+    fn toggle_fold_all(&mut self) {
+        if self.folded.len() < self.sections.len() {
+            self.folded = self.sections.iter().map(|s| s.start).collect();
+            if let Some(start) = self.section_of(self.cursor).map(|s| s.start) {
+                self.cursor = start;
+            }
+        } else {
+            self.folded.clear();
+        }
+    }
+
+    /// 没有被折叠隐藏的行
+    fn visible_lines(&self) -> Vec<usize> {
+        let mut visible = Vec::with_capacity(self.lines.len());
+        let mut i = 0;
+        while i < self.lines.len() {
+            visible.push(i);
+            i = match self.folded.contains(&i) {
+                true => self.section_of(i).map_or(i + 1, |s| s.end),
+                false => i + 1,
+            };
+        }
+        visible
+    }
+
+    /// 光标在可见行中的位置, 光标所在行被隐藏时取其前一个可见行
+    fn cursor_row(&self, visible: &[usize]) -> usize {
+        match visible.binary_search(&self.cursor) {
+            Ok(row) => row,
+            Err(row) => row.saturating_sub(1),
+        }
+    }
+
+    pub fn draw(&mut self, f: &mut Frame, area: Rect, focus: bool) {
+        let title = match (self.searching, self.query.is_empty()) {
+            (true, _) => format!("配置 /{}▏", self.query),
+            (false, false) => format!(
+                "配置 /{} ({} 行匹配, n/N 跳转)",
+                self.query,
+                self.matches.len()
+            ),
+            (false, true) => "配置".to_string(),
+        };
+        let mut b = Block::bordered().title(title);
+        if focus {
+            b = b.border_style(Style::default().fg(Color::Yellow));
+        }
+        let inner = b.inner(area);
+        f.render_widget(b, area);
+        if self.lines.is_empty() {
+            f.render_widget(Paragraph::new("配置文件为空"), inner);
+            return;
+        }
+
+        let visible = self.visible_lines();
+        self.height = inner.height as usize;
+        let row = self.cursor_row(&visible);
+        self.cursor = visible[row];
+        // 保持光标在可显示范围内
+        if row < self.scroll {
+            self.scroll = row;
+        } else if row >= self.scroll + self.height {
+            self.scroll = row + 1 - self.height;
+        }
+        self.scroll = self.scroll.min(visible.len().saturating_sub(1));
+
+        let gutter = self.lines.len().to_string().len();
+        let max_width = self
+            .lines
+            .iter()
+            .map(|l| l.chars().count())
+            .max()
+            .unwrap_or(0);
+        let text_width = (inner.width as usize).saturating_sub(gutter + 3);
+        self.hscroll = self.hscroll.min(max_width.saturating_sub(text_width));
+        let lines: Vec<Line> = visible
+            .iter()
+            .skip(self.scroll)
+            .take(self.height)
+            .map(|&i| self.render_line(i, gutter))
+            .collect();
+        f.render_widget(Paragraph::new(lines), inner);
+
+        let mut state =
+            ScrollbarState::new(visible.len().saturating_sub(self.height)).position(self.scroll);
+        f.render_stateful_widget(
+            Scrollbar::new(ScrollbarOrientation::VerticalRight),
+            area.inner(Margin::new(0, 1)),
+            &mut state,
+        );
+        if max_width > text_width {
+            let mut state =
+                ScrollbarState::new(max_width.saturating_sub(text_width)).position(self.hscroll);
+            f.render_stateful_widget(
+                Scrollbar::new(ScrollbarOrientation::HorizontalBottom).thumb_symbol("■"),
+                area.inner(Margin::new(1, 0)),
+                &mut state,
+            );
+        }
+    }
+
+    /// 一行的行号, 折叠标记和高亮后的内容
+    fn render_line(&self, i: usize, gutter: usize) -> Line<'static> {
+        let line = &self.lines[i];
+        let fold = match self.sections.iter().find(|s| s.start == i) {
+            Some(_) if self.folded.contains(&i) => "▸",
+            Some(_) => "▾",
+            None => " ",
+        };
+        let mut spans = vec![Span::styled(
+            format!("{:>gutter$} {fold} ", i + 1),
+            GUTTER_STYLE,
+        )];
+
+        let chars: Vec<char> = line.chars().collect();
+        let mut styles = syntax_styles(&chars);
+        for range in match_ranges(line, &self.query) {
+            let start = line[..range.start].chars().count();
+            let end = start + line[range].chars().count();
+            styles[start..end].fill(MATCH_STYLE);
+        }
+        let mut text = String::new();
+        let mut style = None;
+        for (c, s) in chars.iter().zip(styles).skip(self.hscroll) {
+            if style.is_some_and(|style| style != s) {
+                spans.push(Span::styled(std::mem::take(&mut text), style.unwrap()));
+            }
+            style = Some(s);
+            text.push(*c);
+        }
+        if let Some(style) = style {
+            spans.push(Span::styled(text, style));
+        }
+        if self.folded.contains(&i) {
+            if let Some(section) = self.section_of(i) {
+                spans.push(Span::styled(
+                    format!(" … {} 行已折叠", section.len() - 1),
+                    COMMENT_STYLE,
+                ));
+            }
+        }
+        let line = Line::from(spans);
+        match i == self.cursor {
+            true => line.style(CURSOR_STYLE),
+            false => line,
+        }
+    }
+}
+
+/// 顶层段落: 从没有缩进的行开始, 到下一个没有缩进的行为止, 末尾的空行和注释不算在内
+fn top_level_sections(lines: &[String]) -> Vec<Range<usize>> {
+    let is_top = |line: &String| {
+        !line.is_empty()
+            && !line.starts_with([' ', '#'])
+            && !line.starts_with("---")
+            && !line.starts_with("...")
+    };
+    let starts: Vec<usize> = (0..lines.len()).filter(|&i| is_top(&lines[i])).collect();
+    starts
+        .iter()
+        .enumerate()
+        .filter_map(|(n, &start)| {
+            let mut end = starts.get(n + 1).copied().unwrap_or(lines.len());
+            while end > start + 1 && {
+                let line = lines[end - 1].trim_start();
+                line.is_empty() || line.starts_with('#')
+            } {
+                end -= 1;
+            }
+            (end - start > 1).then_some(start..end)
+        })
+        .collect()
+}
+
+/// 按字符计算一行的高亮样式, 不处理跨行的字符串
+fn syntax_styles(chars: &[char]) -> Vec<Style> {
+    let mut styles = vec![Style::default(); chars.len()];
+    let mut i = chars.iter().take_while(|c| c.is_whitespace()).count();
+    // 列表标记
+    while i < chars.len() && chars[i] == '-' && chars.get(i + 1).is_none_or(|c| *c == ' ') {
+        styles[i] = MARK_STYLE;
+        i += 1;
+        i += chars[i..].iter().take_while(|c| c.is_whitespace()).count();
+    }
+    let (comment, key_end) = scan(chars, i);
+    styles[comment..].fill(COMMENT_STYLE);
+    let mut value_start = i;
+    if let Some(colon) = key_end {
+        styles[i..colon].fill(KEY_STYLE);
+        value_start = colon + 1;
+    }
+    let value: String = chars[value_start..comment].iter().collect();
+    let trimmed = value.trim();
+    let offset = value_start + value.chars().take_while(|c| c.is_whitespace()).count();
+    let len = trimmed.chars().count();
+    let style = match trimmed {
+        "" => None,
+        _ if trimmed.starts_with(['"', '\'']) => Some(STRING_STYLE),
+        _ if trimmed.starts_with(['&', '*', '|', '>', '!']) => Some(MARK_STYLE),
+        "true" | "false" | "True" | "False" | "TRUE" | "FALSE" | "null" | "Null" | "NULL" | "~" => {
+            Some(BOOL_STYLE)
+        }
+        _ if trimmed.parse::<f64>().is_ok() => Some(NUMBER_STYLE),
+        _ => None,
+    };
+    if let Some(style) = style {
+        styles[offset..offset + len].fill(style);
+    }
+    styles
+}
+
+/// 从 start 开始查找注释的起点和键后的冒号, 忽略引号中的内容
+fn scan(chars: &[char], start: usize) -> (usize, Option<usize>) {
+    let mut quote = None;
+    let mut key_end = None;
+    // flow 风格的集合 ({a: 1}, [a, b]) 整体作为值, 不查找键
+    let flow = chars.get(start).is_some_and(|c| matches!(c, '{' | '['));
+    for i in start..chars.len() {
+        let c = chars[i];
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None => match c {
+                '"' | '\'' if i == start || chars[i - 1] == ' ' => quote = Some(c),
+                '#' if i == start || chars[i - 1].is_whitespace() => return (i, key_end),
+                ':' if !flow
+                    && key_end.is_none()
+                    && chars.get(i + 1).is_none_or(|c| c.is_whitespace()) =>
+                {
+                    key_end = Some(i)
+                }
+                _ => {}
+            },
+        }
+    }
+    (chars.len(), key_end)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const YAML: &str = "mixed-port: 7890\nproxies:\n  - name: \"HK # 01\"\n    port: 443\n\n# 规则\nrules:\n  - MATCH,DIRECT\n";
+
+    #[test]
+    fn fold_sections() {
+        let mut viewer = YamlViewer::new(YAML.to_string());
+        assert_eq!(viewer.sections, vec![1..4, 6..8]);
+        viewer.cursor = 2;
+        viewer.toggle_fold();
+        assert_eq!(viewer.cursor, 1);
+        assert_eq!(viewer.visible_lines(), vec![0, 1, 4, 5, 6, 7]);
+        viewer.move_cursor(1);
+        assert_eq!(viewer.cursor, 4);
+        viewer.toggle_fold_all();
+        assert_eq!(viewer.visible_lines(), vec![0, 1, 4, 5, 6]);
+        viewer.toggle_fold_all();
+        assert_eq!(viewer.visible_lines().len(), 8);
+    }
+
+    #[test]
+    fn search_unfolds_match() {
+        let mut viewer = YamlViewer::new(YAML.to_string());
+        viewer.toggle_fold_all();
+        viewer.handle_key(KeyEvent::from(KeyCode::Char('/')));
+        for c in "match".chars() {
+            viewer.handle_key(KeyEvent::from(KeyCode::Char(c)));
+        }
+        viewer.handle_key(KeyEvent::from(KeyCode::Enter));
+        assert!(!viewer.searching);
+        assert_eq!(viewer.matches, vec![7]);
+        assert_eq!(viewer.cursor, 7);
+        assert!(viewer.visible_lines().contains(&7));
+    }
+
+    #[test]
+    fn highlight_key_value_comment() {
+        let chars: Vec<char> = "  - name: \"HK # 01\" # 注释".chars().collect();
+        let styles = syntax_styles(&chars);
+        assert_eq!(styles[2], MARK_STYLE);
+        assert_eq!(styles[4], KEY_STYLE);
+        assert_eq!(styles[8], Style::default());
+        assert_eq!(styles[10], STRING_STYLE);
+        assert_eq!(styles[14], STRING_STYLE);
+        assert_eq!(styles[20], COMMENT_STYLE);
+        let chars: Vec<char> = "port: 443".chars().collect();
+        assert_eq!(syntax_styles(&chars)[6], NUMBER_STYLE);
+    }
+}